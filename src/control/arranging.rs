@@ -0,0 +1,68 @@
+use piston_window::{Button::*, ButtonArgs, ButtonState::*, Input, Input::*, Key};
+use rust_synth::core::control::tools::Command;
+use crate::control::{Control, Mode};
+
+pub fn handle_input(input: &Input, control: &mut Control) -> Vec<Command> {
+    match input {
+        Button(ButtonArgs { state: Press, button: Keyboard(key), .. }) => handle_press(*key, control),
+        Button(ButtonArgs { state: Release, button: Keyboard(key), .. }) => {
+            if let Key::Tab | Key::Escape | Key::Backquote = key {
+                control.mode = Mode::Playing;
+            }
+            vec![]
+        },
+        _ => vec![],
+    }
+}
+
+fn handle_press(key: Key, control: &mut Control) -> Vec<Command> {
+    if control.is_ctrl_held() {
+        file(key, control);
+        return vec![]
+    }
+    let (bpm, key_playing) = (control.bpm(), control.transposed_key);
+    let song = &mut control.song;
+    let field = song.field;
+    match key {
+        Key::Space => song.toggle_playback(&control.clock),
+        Key::Return => song.add_section(),
+        Key::Backspace => song.remove_section(),
+        Key::Up => song.select(-1),
+        Key::Down => song.select(1),
+        Key::Left => song.select_field(-1),
+        Key::Right => song.select_field(1),
+        Key::Minus => if let Some(section) = song.section_mut() { section.change(field, -1, bpm, key_playing) },
+        Key::Equals => if let Some(section) = song.section_mut() { section.change(field, 1, bpm, key_playing) },
+        Key::Delete => if let Some(section) = song.section_mut() { section.unset(field) },
+        _ => if let Some(n) = loop_key(key) {
+            if let Some(section) = song.section_mut() {
+                section.loops[n] = !section.loops[n];
+            }
+        },
+    }
+    vec![]
+}
+
+fn file(key: Key, control: &mut Control) {
+    let song = &mut control.song;
+    let (result, done) = match key {
+        Key::S => (song.save(), "saved"),
+        Key::O => (song.load(), "loaded"),
+        _ => return,
+    };
+    song.status = Some(match result {
+        Ok(()) => done.to_string(),
+        Err(e) => e.to_string(),
+    });
+}
+
+fn loop_key(key: Key) -> Option<usize> {
+    match key {
+        Key::D1 => Some(0),
+        Key::D2 => Some(1),
+        Key::D3 => Some(2),
+        Key::D4 => Some(3),
+        Key::D5 => Some(4),
+        _ => None,
+    }
+}
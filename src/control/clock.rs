@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+/// Beats shared by everything the GUI plays on the tempo by itself: the sequencer, the rhythm grid, the metronome
+/// and the song. They're counted a little at a time from the synth's pulse, so that a change of tempo
+/// doesn't move them, and are ticked from the GUI's updates, which carry on when the window isn't drawn.
+#[derive(Default)]
+pub struct Clock {
+    beats: f64,
+    last_tick: Option<Instant>,
+}
+
+impl Clock {
+
+    pub fn tick(&mut self, beat: Duration, now: Instant) {
+        if let Some(last_tick) = self.last_tick {
+            self.beats += now.saturating_duration_since(last_tick).as_secs_f64() / beat.as_secs_f64();
+        }
+        self.last_tick = Some(now);
+    }
+
+    /// Beats counted so far
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Takes a tap of the tempo as falling on the nearest beat
+    pub fn tap(&mut self) {
+        self.beats = self.beats.round();
+    }
+
+    /// Next of the steps a beat is divided in, to start playing on
+    pub fn next_step(&self, steps_per_beat: u32) -> f64 {
+        (self.beats * steps_per_beat as f64).ceil() / steps_per_beat as f64
+    }
+}
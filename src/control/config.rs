@@ -0,0 +1,61 @@
+use std::fs;
+use std::io;
+use rust_synth::core::music_theory::Hz;
+
+pub const FILE: &str = "config.txt";
+/// Reference the synth's pitches are tuned to
+pub const CONCERT_A4: Hz = 440.;
+/// Semitones the reference can move either side of concert pitch, the synth playing only whole ones
+pub const MAX_SEMITONES: i32 = 1;
+
+/// Settings kept between runs, as `name=value` lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Frequency of A4, everything else being tuned from it: concert pitch moved by whole semitones
+    pub reference: Hz,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { reference: CONCERT_A4 }
+    }
+}
+
+impl Config {
+
+    /// Settings saved before, leaving out the ones missing or unreadable and rounding the reference to a playable one
+    pub fn load() -> Config {
+        let mut config = Config::default();
+        let text = fs::read_to_string(FILE).unwrap_or_default();
+        for (name, value) in text.lines().filter_map(|line| line.split_once('=')) {
+            if let ("reference", Ok(reference)) = (name.trim(), value.trim().parse()) {
+                config.reference = nearest_reference(reference);
+            }
+        }
+        config
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(FILE, format!("reference={}\n", self.reference))
+    }
+
+    /// How far the tuning is from the synth's, in cents
+    pub fn offset_cents(&self) -> f64 {
+        100. * self.semitones() as f64
+    }
+
+    /// Whole semitones the notes are moved by to follow the reference
+    pub fn semitones(&self) -> i32 {
+        (12. * (self.reference / CONCERT_A4).log2()).round() as i32
+    }
+}
+
+/// Concert pitch moved by whole semitones, as far as the reference goes
+pub fn reference(semitones: i32) -> Hz {
+    CONCERT_A4 * 2f64.powf(semitones.clamp(-MAX_SEMITONES, MAX_SEMITONES) as f64 / 12.)
+}
+
+/// Reference the synth can play nearest to a frequency, or concert pitch when it's not a number
+pub fn nearest_reference(freq: Hz) -> Hz {
+    if freq.is_nan() { CONCERT_A4 } else { reference((12. * (freq / CONCERT_A4).log2()).round() as i32) }
+}
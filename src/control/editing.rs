@@ -1,26 +1,53 @@
-use piston_window::{Input, Input::{Button, Move}, ButtonArgs, ButtonState::*, Button::Keyboard, Key,
-                    Motion, Motion::MouseCursor, ButtonState};
-use crate::control::{Control, Mode, EditTarget, OscillatorTarget};
+use piston_window::{Input, Input::{Button, Move}, ButtonArgs, ButtonState::*, Button::{Keyboard, Mouse}, Key,
+                    Motion, Motion::{MouseCursor, MouseScroll}, ButtonState, MouseButton};
+use crate::control::{Control, Mode, EditTarget, OscillatorTarget, widgets::{self, Drag, Kind}, params::Param,
+                     modulation::{Route, Source, Destination, N_LFOS}, slots::N_LOOPS, tuning::Tuning};
+use crate::control::playing::change_tempo;
 use rust_synth::core::control::synth::Command::SetPatch;
 use rust_synth::core::control::tools::{Command, Patch};
-use rust_synth::core::synth::{oscillator, filter, lfo};
-use rust_synth::core::synth::oscillator::Specs;
+use rust_synth::core::synth::{oscillator, filter};
 use rust_synth::core::tools::arpeggiator;
-use rust_synth::core::music_theory::diatonic_scale::OctaveShift;
-use rust_synth::core::synth::instrument::ModTarget;
 
-pub fn handle_input(input: &Input, window_size: [f64;2], control: &mut Control) -> Vec<Command>{
-    match input {
+/// Vertical distance in pixels to turn a knob from min to max
+const KNOB_DRAG_DISTANCE: f64 = 200.;
+/// Scales mouse movements and continuous steps while shift is held
+const FINE_ADJUST: f64 = 0.1;
+/// Highest random seed that can be picked for a detuned mix, the oscillator page wrapping around after it
+pub const MAX_SEED: u64 = 99;
+
+pub fn handle_input(input: &Input, control: &mut Control) -> Vec<Command>{
+    let mut commands = play_selected_patch(control);
+    commands.extend(match input {
         Button(args) => handle_button(args, control),
-        Move(motion) => handle_mouse(motion, control, window_size),
+        Move(motion) => handle_mouse(motion, control),
         _ => vec![],
+    });
+    commands
+}
+
+/// Hands the synth the patch being edited, so that changes to it can be heard
+fn play_selected_patch(control: &mut Control) -> Vec<Command> {
+    if control.played_patch == control.selected_patch {
+        return vec![]
     }
+    control.played_patch = control.selected_patch;
+    update_specs(control)
 }
 
 fn handle_button(args: &ButtonArgs, control: &mut Control) -> Vec<Command> {
     match (args.state, args.button) {
         (_, Keyboard(Key::Space)) => handle_spacebar(args.state),
-        (Release, Keyboard(key)) => handle_key(key, control),
+        (Release, Keyboard(key)) => {
+            let commands = handle_key(key, control);
+            control.save_config();
+            commands
+        },
+        (Press, Mouse(MouseButton::Left)) => handle_click(control),
+        (Release, Mouse(MouseButton::Left)) => {
+            control.drag = None;
+            control.save_config();
+            vec![]
+        },
         _ => vec![],
     }
 }
@@ -47,7 +74,14 @@ fn handle_key(key: Key, control: &mut Control) -> Vec<Command> {
         Mode::Editing(Some(Filter)) => filter(key, control),
         Mode::Editing(Some(Arpeggiator)) => arpeggiator(key, control),
         Mode::Editing(Some(LFO)) => lfo(key, control),
-        _ => panic!(),
+        Mode::Editing(Some(Matrix)) => matrix(key, control),
+        Mode::Editing(Some(Envelope)) => envelope(key, control),
+        Mode::Editing(Some(Loops)) => loops(key, control),
+        Mode::Editing(Some(Tempo)) => tempo(key, control),
+        Mode::Editing(Some(Scale)) => scale(key, control),
+        Mode::Editing(Some(MouseXY)) | Mode::Editing(Some(Patches)) | Mode::Editing(Some(Voices)) =>
+            main_menu(key, control),
+        _ => vec![],
     }
 }
 
@@ -58,35 +92,72 @@ fn main_menu(key: Key, control: &mut Control) -> Vec<Command> {
         Key::F => control.mode = Mode::Editing(Some(EditTarget::Filter)),
         Key::A => control.mode = Mode::Editing(Some(EditTarget::Arpeggiator)),
         Key::L => control.mode = Mode::Editing(Some(EditTarget::LFO)),
+        Key::M => control.mode = Mode::Editing(Some(EditTarget::Matrix)),
+        Key::E => control.mode = Mode::Editing(Some(EditTarget::Envelope)),
+        Key::X => control.mode = Mode::Editing(Some(EditTarget::MouseXY)),
+        Key::K => control.mode = Mode::Editing(Some(EditTarget::Patches)),
+        Key::P => control.mode = Mode::Editing(Some(EditTarget::Loops)),
+        Key::T => control.mode = Mode::Editing(Some(EditTarget::Tempo)),
+        Key::S => control.mode = Mode::Editing(Some(EditTarget::Scale)),
+        Key::V => control.mode = Mode::Editing(Some(EditTarget::Voices)),
+        Key::R => control.relative_mouse = !control.relative_mouse,
+        Key::Up => return nudge_focus(1., control),
+        Key::Down => return nudge_focus(-1., control),
         _ => (),
     }
     vec![]
 }
 
+fn nudge_focus(steps: f64, control: &mut Control) -> Vec<Command> {
+    match control.focus {
+        Some(param) => nudge(param, steps, control),
+        None => vec![],
+    }
+}
+
+/// Moves a param by whole steps along its curve or options, or fractions of a step on continuous curves with shift held
+fn nudge(param: Param, steps: f64, control: &mut Control) -> Vec<Command> {
+    let options = param.options();
+    if !options.is_empty() {
+        let selected = control.selected(param);
+        let index = if steps > 0. { (selected + 1).min(options.len() - 1) } else { selected.saturating_sub(1) };
+        control.select(param, index);
+    } else {
+        let curve = param.curve();
+        let step = if curve.is_stepped() { curve.step() } else { curve.step() * fine_adjust(control) };
+        control.set_value(param, control.value(param) + steps * step);
+    }
+    update_specs(control)
+}
+
 fn oscillator(key: Key, control: &mut Control) -> Vec<Command> {
-    let mut set = |f: fn(&oscillator::Specs) -> oscillator::Specs, edit_target: Option<OscillatorTarget>| {
-        control.instrument.oscillator = f(&control.instrument.oscillator);
+    let patch = &control.patches[control.selected_patch];
+    let mix = patch.mix();
+    let reseed = !patch.seed_locked && mix == patch.instrument.oscillator;
+    let mut set = |specs: oscillator::Specs, edit_target: Option<OscillatorTarget>| {
+        control.patches[control.selected_patch].set_oscillator(specs);
         control.mode =  Mode::Editing(Some(EditTarget::Oscillator(edit_target)));
         update_specs(control)
     };
 
-    fn next_mix(old: &oscillator::Specs) -> oscillator::Specs {
-        use oscillator::{Specs::*, Basic::*};
-        match *old {
-            Mix { n_voices, detune_amount, specs, random_seed, .. } =>
-                Mix { n_voices, detune_amount, specs, random_seed: random_seed + 1 },
-            _ => Mix{ n_voices: 8, detune_amount: 3., specs: Saw, random_seed: 0 },
+    /// Another seed for the mix playing when it's not locked, the mix coming back as it was otherwise
+    fn next_mix(mix: oscillator::Specs, reseed: bool) -> oscillator::Specs {
+        use oscillator::Specs::*;
+        match mix {
+            Mix { n_voices, detune_amount, specs, random_seed, .. } if reseed =>
+                Mix { n_voices, detune_amount, specs, random_seed: (random_seed + 1) % (MAX_SEED + 1) },
+            mix => mix,
         }
     }
 
     use oscillator::{Specs::*, Basic::*};
     match key {
-        Key::D1 => set(|_| Basic(Sine), None),
-        Key::D2 => set(|_| Basic(Saw), None),
-        Key::D3 => set(|_| Basic(Square), None),
-        Key::D4 => set(|_| Pulse(0.5), Some(OscillatorTarget::Pulse)),
+        Key::D1 => set(Basic(Sine), None),
+        Key::D2 => set(Basic(Saw), None),
+        Key::D3 => set(Basic(Square), None),
+        Key::D4 => set(Pulse(0.5), Some(OscillatorTarget::Pulse)),
         Key::D5 => {
-            set(|o| next_mix(o), Some(OscillatorTarget::Mix))
+            set(next_mix(mix, reseed), Some(OscillatorTarget::Mix))
         },
         _ => main_menu(key,control),
     }
@@ -94,7 +165,7 @@ fn oscillator(key: Key, control: &mut Control) -> Vec<Command> {
 
 fn filter(key: Key, control: &mut Control) -> Vec<Command> {
     let mut set = |spec: filter::TypeSpec| {
-        control.instrument.filter = filter::Specs{ filter_type: spec, .. Default::default() };
+        control.instrument_mut().filter = filter::Specs{ filter_type: spec, .. Default::default() };
         update_specs(control)
     };
     use filter::TypeSpec::*;
@@ -142,118 +213,211 @@ fn arpeggiator(key: Key, control: &mut Control) -> Vec<Command> {
 }
 
 fn lfo(key: Key, control: &mut Control) -> Vec<Command> {
-    use lfo::Specs;
-    use ModTarget::*;
-    use oscillator::{Specs::Basic, Basic::*, ModTarget::*};
-    use filter::ModTarget::*;
-    let mut set = |f: fn(Specs) -> Specs| {
-        let old = control.instrument.lfo.clone().unwrap_or_else(|| Specs::default());
-        control.instrument.lfo = Some(f(old));
+    use Param::*;
+    let mut select = |param: Param, index: usize| {
+        control.select(param, index);
         update_specs(control)
     };
     match key {
-        Key::D1 => set(|old| Specs { target: Volume, ..old}),
-        Key::D2 => set(|old| Specs { target: Filter(Cutoff), ..old}),
-        Key::D3 => set(|old| Specs { target: Filter(QFactor), ..old}),
-        Key::D4 => set(|old| Specs { target: Oscillator(PulseDuty), ..old}),
-        Key::F1 => set(|old| Specs { oscillator: Basic(Sine), ..old}),
-        Key::F2 => set(|old| Specs { oscillator: Basic(Saw), ..old}),
-        Key::F3 => set(|old| Specs { oscillator: Basic(Square), ..old}),
+        Key::D1 => select(LfoTarget, Destination::Volume.index()),
+        Key::D2 => select(LfoTarget, Destination::Cutoff.index()),
+        Key::D3 => select(LfoTarget, Destination::Resonance.index()),
+        Key::D4 => select(LfoTarget, Destination::PulseDuty.index()),
+        Key::F1 => select(LfoWaveform, 0),
+        Key::F2 => select(LfoWaveform, 1),
+        Key::F3 => select(LfoWaveform, 2),
+        Key::Left => {
+            control.selected_lfo = control.selected_lfo.saturating_sub(1);
+            vec![]
+        },
+        Key::Right => {
+            control.selected_lfo = (control.selected_lfo + 1).min(N_LFOS - 1);
+            vec![]
+        },
         Key::D0 => {
-            control.instrument.lfo = None;
+            control.set_value(LfoOn, 0.);
             update_specs(control)
         },
         _ => main_menu(key,control),
     }
 }
 
-fn handle_mouse(motion: &Motion, control: &mut Control, window_size: [f64;2]) -> Vec<Command> {
-    match motion {
-        MouseCursor(x, y) =>  handle_move(*x, *y, control, window_size),
-        _ => ()
+fn matrix(key: Key, control: &mut Control) -> Vec<Command> {
+    match key {
+        Key::Equals => {
+            let route = Route { source: Source::Lfo(0), destination: Destination::Cutoff, amount: 0.5 };
+            control.modulation.add_route(route);
+            update_specs(control)
+        },
+        Key::Minus => {
+            control.modulation.routes.pop();
+            update_specs(control)
+        },
+        _ => main_menu(key,control),
     }
-    update_specs(control)
 }
 
-fn handle_move(x: f64, y: f64, control: &mut Control, window_size: [f64;2]) {
-    let [norm_x, norm_y] = normalized_mouse(x, y, window_size);
-    use {EditTarget::*, OscillatorTarget::*};
-    match control.mode {
-        Mode::Editing(Some(Oscillator(Some(Pulse)))) =>
-            match &mut control.instrument.oscillator {
-                Specs::Pulse(cycle) =>
-                    change_f64(cycle, norm_x, 0., 1.),
-                _ => {}
-            },
-        Mode::Editing(Some(Oscillator(Some(Mix)))) =>
-            match &mut control.instrument.oscillator {
-                Specs::Mix { n_voices, detune_amount, .. } => {
-                    change_usize(n_voices, norm_y, 1, 40);
-                    change_f64(detune_amount, norm_x, 0.001, 32.);
-                },
-                _ => {}
+fn envelope(key: Key, control: &mut Control) -> Vec<Command> {
+    use Param::*;
+    match key {
+        Key::D1 => control.focus = Some(EnvAttack),
+        Key::D2 => control.focus = Some(EnvDecay),
+        Key::D3 => control.focus = Some(EnvSustain),
+        Key::D4 => control.focus = Some(EnvRelease),
+        _ => return main_menu(key,control),
+    }
+    vec![]
+}
+
+fn loops(key: Key, control: &mut Control) -> Vec<Command> {
+    let n = control.selected_loop;
+    match key {
+        Key::Left => control.selected_loop = n.saturating_sub(1),
+        Key::Right => control.selected_loop = (n + 1).min(N_LOOPS - 1),
+        _ => return main_menu(key,control),
+    }
+    vec![]
+}
+
+/// Besides the tempo keys of playing mode, a tempo can be typed and entered
+fn tempo(key: Key, control: &mut Control) -> Vec<Command> {
+    let digit = match key {
+        Key::D0 => Some('0'), Key::D1 => Some('1'), Key::D2 => Some('2'), Key::D3 => Some('3'), Key::D4 => Some('4'),
+        Key::D5 => Some('5'), Key::D6 => Some('6'), Key::D7 => Some('7'), Key::D8 => Some('8'), Key::D9 => Some('9'),
+        _ => None,
+    };
+    match (digit, key) {
+        (Some(digit), _) if control.typed_bpm.len() < 3 => control.typed_bpm.push(digit),
+        (Some(_), _) => (),
+        (_, Key::Backspace) => { control.typed_bpm.pop(); },
+        (_, Key::Return) => {
+            if let Ok(bpm) = control.typed_bpm.parse::<f64>() {
+                control.tapper.set_bpm(bpm);
+            }
+            control.typed_bpm.clear();
+        },
+        (_, Key::Minus) | (_, Key::Equals) | (_, Key::LeftBracket) | (_, Key::RightBracket) =>
+            change_tempo(key, control),
+        _ => return main_menu(key, control),
+    }
+    vec![]
+}
+
+/// Loads a Scala tuning from the working directory, or drops it to go back to the scale
+fn scale(key: Key, control: &mut Control) -> Vec<Command> {
+    match key {
+        Key::Return => match Tuning::load() {
+            Ok(tuning) => {
+                control.tuning_status = Some(format!("loaded {}, rounded to semitones", tuning.description));
+                control.tuning = Some(tuning);
             },
-        Mode::Editing(Some(Filter)) => {
-            change_f64(&mut control.instrument.filter.cutoff, norm_y, 0., 1.);
-            change_f64(&mut control.instrument.filter.resonance, norm_x, 0., 1.);
+            Err(e) => control.tuning_status = Some(e.to_string()),
         },
-        Mode::Editing(Some(Arpeggiator)) => {
-            use arpeggiator::builder::Specs;
-            match &mut control.arpeggiator {
-                Some(arp) => {
-                    let old = &arp.phrase;
-                    let (octave_min, octave_max) = octaves_from_mouse(norm_x, norm_y);
-                    arp.phrase = Specs { octave_min, octave_max, ..old.clone() };
-                }
-                _ => {}
-            }
+        Key::Backspace => {
+            control.tuning = None;
+            control.tuning_status = None;
         },
-        Mode::Editing(Some(LFO)) => {
-            match &mut control.instrument.lfo {
-                Some(lfo) => {
-                    change_f64(&mut lfo.amount, norm_y, 0., 1.);
-                    change_f64(&mut lfo.freq, norm_x, 0., 55.); //TODO normalize, exponential scale
-                }
-                _ => {}
+        _ => return main_menu(key, control),
+    }
+    vec![]
+}
+
+fn handle_click(control: &mut Control) -> Vec<Command> {
+    let [x, y] = control.cursor;
+    let widgets = widgets::layout(control);
+    if let Some(param) = control.open_dropdown.take() {
+        let option = widgets.iter()
+            .find(|w| w.param == param)
+            .and_then(|w| widgets::hit_option(w, x, y));
+        return match option {
+            Some(index) => {
+                control.select(param, index);
+                update_specs(control)
+            },
+            None => vec![],
+        }
+    }
+    match widgets::hit(&widgets, x, y) {
+        Some(widget) => {
+            let param = widget.param;
+            control.focus = Some(param);
+            match widget.kind {
+                Kind::Knob => {
+                    start_drag(widget, control);
+                    vec![]
+                },
+                Kind::Slider => {
+                    start_drag(widget, control);
+                    if control.relative_mouse {
+                        vec![]
+                    } else {
+                        control.set_value(param, widgets::slider_value(&widget, x));
+                        update_specs(control)
+                    }
+                },
+                Kind::Toggle => {
+                    control.set_value(param, 1. - control.value(param));
+                    update_specs(control)
+                },
+                Kind::Dropdown => {
+                    control.open_dropdown = Some(param);
+                    vec![]
+                },
             }
         },
-        _ => (),
+        None => vec![],
     }
 }
 
-fn normalized_mouse(x: f64, y: f64, window_size: [f64;2]) -> [f64; 2] {
-    let normalized_x = x / window_size[0];
-    let normalized_y = 1. - (y / window_size[1]);
-    [normalized_x.min(1.).max(0.),
-        normalized_y.min(1.).max(0.)]
+fn start_drag(widget: widgets::Widget, control: &mut Control) {
+    let value = control.value(widget.param);
+    control.drag = Some(Drag { widget, last: control.cursor, value });
 }
 
-fn change_f64(reference: &mut f64, normalized: f64, min: f64, max: f64) {
-    let scaled = normalized * (max - min) + min;
-    *reference = scaled;
+fn handle_mouse(motion: &Motion, control: &mut Control) -> Vec<Command> {
+    match motion {
+        MouseCursor(x, y) => handle_move(*x, *y, control),
+        MouseScroll(_, y) => handle_scroll(*y, control),
+        _ => vec![],
+    }
+}
+
+fn handle_move(x: f64, y: f64, control: &mut Control) -> Vec<Command> {
+    match control.drag {
+        Some(mut drag) => {
+            let [last_x, last_y] = drag.last;
+            let scale = fine_adjust(control);
+            let value = match drag.widget.kind {
+                Kind::Slider if !control.relative_mouse => widgets::slider_value(&drag.widget, x),
+                Kind::Slider => drag.value + (x - last_x) / drag.widget.rect[2] * scale,
+                _ => drag.value + (last_y - y) / KNOB_DRAG_DISTANCE * scale,
+            };
+            drag.value = value.clamp(0., 1.);
+            drag.last = [x, y];
+            control.drag = Some(drag);
+            control.set_value(drag.widget.param, drag.value);
+            update_specs(control)
+        },
+        None => vec![],
+    }
 }
 
-fn change_usize(reference: &mut usize, normalized: f64, min: usize, max: usize) {
-    let range = (max - min) as f64;
-    let scaled = (normalized * range).floor() as usize + min;
-    *reference = scaled;
+fn handle_scroll(steps: f64, control: &mut Control) -> Vec<Command> {
+    let [x, y] = control.cursor;
+    match widgets::hit(&widgets::layout(control), x, y) {
+        Some(widget) => {
+            control.focus = Some(widget.param);
+            nudge(widget.param, steps, control)
+        },
+        None => vec![],
+    }
 }
 
-fn octaves_from_mouse(x: f64, y: f64) -> (OctaveShift, OctaveShift) {
-    let max_range = OctaveShift::Up3 as i8 - OctaveShift::Down3 as i8;
-    let range = (x * max_range as f64).floor() as i8;
-    let min_offset = OctaveShift::Down3 as i8;
-    let max_offset = OctaveShift::Up3 as i8;
-    let offset = (y * max_range as f64 + min_offset as f64).floor() as i8;
-    let bottom_octave = OctaveShift::from_i8(offset)
-        .unwrap_or_else(|| panic!("Can't get OctaveShift from {}", offset));
-    let top_octave_i8 = (offset + range).min(max_offset);
-    let top_octave = OctaveShift::from_i8(top_octave_i8)
-        .unwrap_or_else(|| panic!("Can't get OctaveShift from {}", top_octave_i8));
-    (bottom_octave, top_octave)
+fn fine_adjust(control: &Control) -> f64 {
+    if control.is_shift_held() { FINE_ADJUST } else { 1. }
 }
 
-fn update_specs(control: &Control) -> Vec<Command> {
-    vec![Command::Instrument(SetPatch(control.instrument.clone())),
+pub fn update_specs(control: &Control) -> Vec<Command> {
+    vec![Command::Instrument(SetPatch(control.patch())),
          Command::SetPatch(Patch::Arpeggiator(control.arpeggiator.clone()))]
 }
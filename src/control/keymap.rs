@@ -0,0 +1,89 @@
+use piston_window::Key;
+use rust_synth::core::{
+    control::synth::Discriminator,
+    music_theory::{pitch::Pitch, pitch_class::PitchClass::*},
+};
+
+/// Note keys as they are laid out on a QWERTY keyboard, top row first
+pub const ROWS: [[(Key, &str); 10]; 3] = [
+    [(Key::Q, "Q"), (Key::W, "W"), (Key::E, "E"), (Key::R, "R"), (Key::T, "T"),
+     (Key::Y, "Y"), (Key::U, "U"), (Key::I, "I"), (Key::O, "O"), (Key::P, "P")],
+    [(Key::A, "A"), (Key::S, "S"), (Key::D, "D"), (Key::F, "F"), (Key::G, "G"),
+     (Key::H, "H"), (Key::J, "J"), (Key::K, "K"), (Key::L, "L"), (Key::Semicolon, ";")],
+    [(Key::Z, "Z"), (Key::X, "X"), (Key::C, "C"), (Key::V, "V"), (Key::B, "B"),
+     (Key::N, "N"), (Key::M, "M"), (Key::Comma, ","), (Key::Period, "."), (Key::Slash, "/")],
+];
+
+pub fn pitches(key: Key) -> Option<(Pitch, Discriminator)> { //TODO shift => sharp pitches
+    match key {
+        //top row
+        Key::Q =>         Some((Pitch::new(A, 4), 3)),
+        Key::W =>         Some((Pitch::new(B, 4), 3)),
+        Key::E =>         Some((Pitch::new(C, 5), 3)),
+        Key::R =>         Some((Pitch::new(D, 5), 3)),
+        Key::T =>         Some((Pitch::new(E, 5), 3)),
+        Key::Y =>         Some((Pitch::new(F, 5), 3)),
+        Key::U =>         Some((Pitch::new(G, 5), 3)),
+        Key::I =>         Some((Pitch::new(A, 5), 3)),
+        Key::O =>         Some((Pitch::new(B, 5), 3)),
+        Key::P =>         Some((Pitch::new(C, 6), 3)),
+
+        //middle row
+        Key::A =>         Some((Pitch::new(A, 3), 2)),
+        Key::S =>         Some((Pitch::new(B, 3), 2)),
+        Key::D =>         Some((Pitch::new(C, 4), 2)),
+        Key::F =>         Some((Pitch::new(D, 4), 2)),
+        Key::G =>         Some((Pitch::new(E, 4), 2)),
+        Key::H =>         Some((Pitch::new(F, 4), 2)),
+        Key::J =>         Some((Pitch::new(G, 4), 2)),
+        Key::K =>         Some((Pitch::new(A, 4), 2)),
+        Key::L =>         Some((Pitch::new(B, 4), 2)),
+        Key::Semicolon => Some((Pitch::new(C, 5), 2)),
+
+        //bottom row
+        Key::Z =>         Some((Pitch::new(A, 2), 1)),
+        Key::X =>         Some((Pitch::new(B, 2), 1)),
+        Key::C =>         Some((Pitch::new(C, 3), 1)),
+        Key::V =>         Some((Pitch::new(D, 3), 1)),
+        Key::B =>         Some((Pitch::new(E, 3), 1)),
+        Key::N =>         Some((Pitch::new(F, 3), 1)),
+        Key::M =>         Some((Pitch::new(G, 3), 1)),
+        Key::Comma =>     Some((Pitch::new(A, 3), 1)),
+        Key::Period =>    Some((Pitch::new(B, 3), 1)),
+        Key::Slash =>     Some((Pitch::new(C, 4), 1)),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_laid_out_key_plays() {
+        for (key, label) in ROWS.iter().flatten() {
+            assert!(pitches(*key).is_some(), "{} plays nothing", label);
+        }
+        assert_eq!(pitches(Key::Space), None);
+    }
+
+    #[test]
+    fn keys_rise_left_to_right() {
+        for row in ROWS.iter() {
+            for pair in row.windows(2) {
+                let (left, _) = pitches(pair[0].0).unwrap();
+                let (right, _) = pitches(pair[1].0).unwrap();
+                assert!(left.freq() < right.freq(), "{} isn't below {}", pair[0].1, pair[1].1);
+            }
+        }
+    }
+
+    #[test]
+    fn rows_sharing_a_pitch_tell_their_keys_apart() {
+        assert_eq!(pitches(Key::K).unwrap().0, pitches(Key::Q).unwrap().0);
+        assert_ne!(pitches(Key::K), pitches(Key::Q));
+        assert_eq!(pitches(Key::Comma).unwrap().0, pitches(Key::A).unwrap().0);
+        assert_ne!(pitches(Key::Comma), pitches(Key::A));
+    }
+}
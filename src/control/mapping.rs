@@ -0,0 +1,170 @@
+use crate::control::tempo::DIVISIONS;
+
+/// How the normalized position of a control, between 0 and 1, spreads over the range of a param.
+/// Mouse, keyboard and any other input move params through these, so they all feel the same.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Linear { min: f64, max: f64 },
+    /// Finer near `min`, for frequencies and other values perceived as ratios
+    Exponential { min: f64, max: f64 },
+    /// Finer near `max`
+    Logarithmic { min: f64, max: f64 },
+    /// Whole numbers only
+    Stepped { min: f64, max: f64 },
+    /// Note divisions of the tempo, from the longest to the shortest, as indexes into `tempo::DIVISIONS`
+    TempoSynced,
+}
+
+/// Ratio between the slopes at both ends of the exponential and logarithmic curves
+const CURVATURE: f64 = 100.;
+
+/// Names of the continuous curves, by `shape()`
+pub const SHAPES: [&str; 3] = ["linear", "exponential", "logarithmic"];
+
+/// Fraction of the range moved by one step of the arrow keys or scroll wheel, on continuous curves
+const STEP: f64 = 0.02;
+
+impl Curve {
+
+    pub fn to_value(self, normalized: f64) -> f64 {
+        use Curve::*;
+        let normalized = normalized.clamp(0., 1.);
+        match self {
+            Linear { min, max } => lerp(normalized, min, max),
+            Exponential { min, max } => lerp(exponential(normalized), min, max),
+            Logarithmic { min, max } => lerp(logarithmic(normalized), min, max),
+            Stepped { min, max } => lerp(normalized, min, max).round(),
+            TempoSynced => lerp(normalized, 0., last_division()).round(),
+        }
+    }
+
+    pub fn to_normalized(self, value: f64) -> f64 {
+        use Curve::*;
+        match self {
+            Linear { min, max } | Stepped { min, max } => unlerp(value, min, max),
+            Exponential { min, max } => logarithmic(unlerp(value, min, max)),
+            Logarithmic { min, max } => exponential(unlerp(value, min, max)),
+            TempoSynced => unlerp(value, 0., last_division()),
+        }
+    }
+
+    /// Normalized distance between neighbouring values when stepping through them
+    pub fn step(self) -> f64 {
+        match self {
+            Curve::Stepped { min, max } => 1. / (max - min),
+            Curve::TempoSynced => 1. / last_division(),
+            _ => STEP,
+        }
+    }
+
+    pub fn is_stepped(self) -> bool {
+        matches!(self, Curve::Stepped { .. } | Curve::TempoSynced)
+    }
+
+    pub fn range(self) -> (f64, f64) {
+        use Curve::*;
+        match self {
+            Linear { min, max } | Exponential { min, max } | Logarithmic { min, max } | Stepped { min, max } =>
+                (min, max),
+            TempoSynced => (0., last_division()),
+        }
+    }
+
+    /// Index among `SHAPES`
+    pub fn shape(self) -> usize {
+        match self {
+            Curve::Exponential { .. } => 1,
+            Curve::Logarithmic { .. } => 2,
+            _ => 0,
+        }
+    }
+
+    /// Continuous curve of one of the `SHAPES` over the given range
+    pub fn with_shape(shape: usize, min: f64, max: f64) -> Curve {
+        match shape {
+            1 => Curve::Exponential { min, max },
+            2 => Curve::Logarithmic { min, max },
+            _ => Curve::Linear { min, max },
+        }
+    }
+}
+
+fn last_division() -> f64 {
+    (DIVISIONS.len() - 1) as f64
+}
+
+fn exponential(normalized: f64) -> f64 {
+    (CURVATURE.powf(normalized) - 1.) / (CURVATURE - 1.)
+}
+
+fn logarithmic(normalized: f64) -> f64 {
+    (1. + (CURVATURE - 1.) * normalized).ln() / CURVATURE.ln()
+}
+
+fn lerp(normalized: f64, min: f64, max: f64) -> f64 {
+    normalized * (max - min) + min
+}
+
+fn unlerp(value: f64, min: f64, max: f64) -> f64 {
+    ((value - min) / (max - min)).clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 4] = [
+        Curve::Linear { min: -1., max: 1. },
+        Curve::Exponential { min: 20., max: 20000. },
+        Curve::Logarithmic { min: 0., max: 5. },
+        Curve::Stepped { min: 0., max: 10. },
+    ];
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn ends_map_to_the_range() {
+        for curve in CURVES.iter() {
+            let (min, max) = curve.range();
+            assert_close(curve.to_value(0.), min);
+            assert_close(curve.to_value(1.), max);
+            assert_close(curve.to_value(-1.), min);
+            assert_close(curve.to_value(2.), max);
+        }
+    }
+
+    #[test]
+    fn normalized_round_trip() {
+        for curve in CURVES.iter().filter(|curve| !curve.is_stepped()) {
+            for i in 0..=10 {
+                let normalized = i as f64 / 10.;
+                assert_close(curve.to_normalized(curve.to_value(normalized)), normalized);
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_is_finer_near_min() {
+        let curve = Curve::Exponential { min: 0., max: 1. };
+        assert!(curve.to_value(0.5) < 0.5);
+        let curve = Curve::Logarithmic { min: 0., max: 1. };
+        assert!(curve.to_value(0.5) > 0.5);
+    }
+
+    #[test]
+    fn stepped_rounds_to_whole_steps() {
+        let curve = Curve::Stepped { min: 0., max: 10. };
+        assert_eq!(curve.to_value(0.34), 3.);
+        assert_close(curve.step(), 0.1);
+        assert_close(Curve::TempoSynced.to_value(1.), last_division());
+    }
+
+    #[test]
+    fn shapes_round_trip() {
+        for shape in 0..SHAPES.len() {
+            assert_eq!(Curve::with_shape(shape, 0., 1.).shape(), shape);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rust_synth::core::{control::tools::Command::{self, *}, tools::loops::Command::ToggleRecording};
+use crate::control::clock::Clock;
+
+pub const MIN_BEATS_PER_BAR: u32 = 2;
+pub const MAX_BEATS_PER_BAR: u32 = 7;
+
+/// Flashes the beats of the clock on screen, counting bars from its first beat.
+/// It doesn't click through the synth, whose instrument would play the clicks and loops record them.
+pub struct Metronome {
+    pub on: bool,
+    /// Whether recording a loop waits for a bar of flashed beats first
+    pub count_in: bool,
+    pub beats_per_bar: u32,
+    /// Loop that starts recording once the count-in is over, and on which beat
+    counting_in: Option<(usize, f64)>,
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Metronome { on: false, count_in: false, beats_per_bar: 4, counting_in: None }
+    }
+}
+
+impl Metronome {
+
+    /// Counts a bar from the clock's next beat, then starts recording the loop
+    pub fn start_count_in(&mut self, n: usize, clock: &Clock) {
+        self.counting_in = Some((n, clock.next_step(1) + self.beats_per_bar as f64));
+    }
+
+    pub fn is_counting_in(&self) -> bool {
+        self.counting_in.is_some()
+    }
+
+    /// The recording a count-in leads to, once it's over
+    pub fn tick(&mut self, clock: &Clock) -> Vec<Command> {
+        let mut commands = vec![];
+        if let Some((n, at)) = self.counting_in {
+            if clock.beats() >= at {
+                commands.push(Loop(ToggleRecording(n)));
+                self.counting_in = None;
+            }
+        }
+        commands
+    }
+
+    /// Whether the beat shown is a downbeat, for a moment after each beat while it's on or counting in
+    pub fn flash(&self, clock: &Clock) -> Option<bool> {
+        if !self.on && !self.is_counting_in() {
+            return None
+        }
+        let beats = clock.beats();
+        if beats.fract() < 0.25 {
+            Some(beats.rem_euclid(self.beats_per_bar as f64) < 1.)
+        } else {
+            None
+        }
+    }
+}
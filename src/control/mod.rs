@@ -1,32 +1,340 @@
-use piston_window::Input;
-use rust_synth::core::control::tools::Command;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use piston_window::{Input, Button, ButtonArgs, ButtonState, Key, Motion::MouseCursor};
+use rust_synth::core::control::tools::{Command, View};
+use rust_synth::core::control::synth::Discriminator;
+use rust_synth::core::music_theory::{Semitones, pitch::Pitch, diatonic_scale::Key as MusicKey};
+use modulation::Axis;
+use patches::{Patch, N_PATCHES};
 use rust_synth::core::synth::instrument;
-use rust_synth::core::tools::arpeggiator;
+use rust_synth::core::tools::{arpeggiator, loops, transposer};
 
+pub mod clock;
+pub mod config;
+pub mod keymap;
+pub mod mapping;
+pub mod metronome;
+pub mod modulation;
+pub mod params;
+pub mod patches;
+pub mod quantize;
+pub mod rhythm_grid;
+pub mod scales;
+pub mod sequencer;
+pub mod slots;
+pub mod song;
+pub mod tempo;
+pub mod tuning;
+pub mod voices;
+pub mod widgets;
 mod playing;
 mod editing;
+mod sequencing;
+mod rhythm;
+mod arranging;
 
 #[derive(Copy, Clone, Debug)]
 pub enum Mode {
-    Editing(Option<EditTarget>), Playing
+    Editing(Option<EditTarget>), Playing, Sequencing, Rhythm, Arranging
 }
 
 pub struct Control {
     pub mode: Mode,
-    instrument: instrument::Specs,
+    pub pressed_keys: HashSet<Key>,
+    pub open_dropdown: Option<params::Param>,
+    /// Last param touched with the mouse, moved by the arrow keys
+    pub focus: Option<params::Param>,
+    pub relative_mouse: bool,
+    pub modulation: modulation::Matrix,
+    /// LFO shown in the LFO page
+    pub selected_lfo: usize,
+    pub voices: voices::Voices,
+    /// Semitones the held notes are transposed by the step keys or the mouse
+    pub step_transpose: Semitones,
+    /// Scale the note keys play in
+    pub scale: scales::Scale,
+    /// Tuning loaded from Scala files, played rounded to semitones instead of the scale
+    pub tuning: Option<tuning::Tuning>,
+    /// Outcome of the last tuning file loaded, or of saving the reference pitch
+    pub tuning_status: Option<String>,
+    /// Reference pitch, kept between runs
+    pub config: config::Config,
+    /// Config as last saved, so that it's only written once changed
+    saved_config: config::Config,
+    /// Whether the mouse transposes the held notes instead of modulating the XY targets
+    pub mouse_transposes: bool,
+    /// Mouse position last sent to the XY targets, normalized
+    pub mouse_xy: [f64; 2],
+    /// Holds the XY modulation where it is while the mouse moves
+    pub xy_frozen: bool,
+    pub patches: Vec<Patch>,
+    /// Patch edited by the oscillator, filter and envelope pages
+    pub selected_patch: usize,
+    /// Patch the synth has, as it plays one at a time
+    played_patch: usize,
+    pub loops: Vec<slots::Slot>,
+    /// Loop shown in the loops page
+    pub selected_loop: usize,
+    pub quantizer: quantize::Quantizer,
+    /// Beats everything played on the tempo by the GUI keeps to
+    pub clock: clock::Clock,
+    pub metronome: metronome::Metronome,
+    pub tapper: tempo::Tapper,
+    /// Tempo being typed in the tempo page
+    pub typed_bpm: String,
+    pub sequencer: sequencer::Sequencer,
+    pub rhythm_grid: rhythm_grid::RhythmGrid,
+    pub song: song::Song,
+    /// Key the synth transposes to, as last seen in its view
+    transposed_key: MusicKey,
+    /// Period of the pulse, as last seen in the synth's view
+    beat: Option<Duration>,
+    cursor: [f64; 2],
+    drag: Option<widgets::Drag>,
     arpeggiator: Option<arpeggiator::Specs>,
 }
 
 impl Control {
 
     pub fn new() -> Self {
-        Self { mode: Mode::Playing, instrument: Default::default(), arpeggiator: None }
+        let config = config::Config::load();
+        Self {
+            mode: Mode::Playing,
+            pressed_keys: HashSet::new(),
+            open_dropdown: None,
+            focus: None,
+            relative_mouse: true,
+            modulation: Default::default(),
+            selected_lfo: 0,
+            voices: Default::default(),
+            step_transpose: 0,
+            scale: Default::default(),
+            tuning: None,
+            tuning_status: None,
+            saved_config: config.clone(),
+            config,
+            mouse_transposes: false,
+            mouse_xy: [0., 0.],
+            xy_frozen: false,
+            patches: vec![Patch::default(); N_PATCHES],
+            selected_patch: 0,
+            played_patch: 0,
+            loops: vec![Default::default(); slots::N_LOOPS],
+            selected_loop: 0,
+            quantizer: Default::default(),
+            clock: Default::default(),
+            metronome: Default::default(),
+            tapper: Default::default(),
+            typed_bpm: String::new(),
+            sequencer: Default::default(),
+            rhythm_grid: Default::default(),
+            song: Default::default(),
+            transposed_key: MusicKey::C,
+            beat: None,
+            cursor: [0., 0.],
+            drag: None,
+            arpeggiator: None,
+        }
     }
 
     pub fn handle_input(&mut self, input: &Input, window_size: [f64;2]) -> Vec<Command> {
-        match self.mode {
-            Mode::Editing(_) => editing::handle_input(&input, window_size, self),
+        self.track_input(input);
+        let commands = match self.mode {
+            Mode::Editing(_) => editing::handle_input(&input, self),
             Mode::Playing => playing::handle_input(&input, window_size, self),
+            Mode::Sequencing => sequencing::handle_input(input, self),
+            Mode::Rhythm => rhythm::handle_input(input, self),
+            Mode::Arranging => arranging::handle_input(input, self),
+        };
+        let mut commands = self.quantize(commands);
+        if !matches!(self.mode, Mode::Playing) && self.step_transpose != 0 {
+            commands.extend(playing::set_step_transpose(0, self));
+        }
+        commands
+    }
+
+    /// Puts notes on the grid while a loop records
+    fn quantize(&mut self, commands: Vec<Command>) -> Vec<Command> {
+        let origin = self.loops.iter().find_map(|slot| slot.recording_since());
+        self.quantizer.schedule(commands, origin.zip(self.beat), Instant::now())
+    }
+
+    /// Commands timed by the GUI, sent as their time comes
+    pub fn tick(&mut self, now: Instant) -> Vec<Command> {
+        if let Some(beat) = self.beat {
+            self.clock.tick(beat, now);
+        }
+        let mut commands = self.quantizer.due(now);
+        commands.extend(self.metronome.tick(&self.clock));
+        commands.extend(self.sequencer.tick(&self.clock));
+        commands.extend(self.rhythm_grid.tick(&self.clock));
+        commands.extend(self.follow_song());
+        if self.tapper.tick(now) {
+            commands.push(Command::TapTempo);
+        }
+        commands
+    }
+
+    /// Follows changes in the synth's view, like the tempo, that affect the patch
+    pub fn handle_view(&mut self, view: &View) -> Vec<Command> {
+        let now = Instant::now();
+        self.transposed_key = view.transposer.transposed_key;
+        let mut commands = self.follow_loops(&view.loops);
+        commands.extend(self.follow_tempo(view.pulse.period));
+        self.tapper.follow(view.pulse.period, now);
+        commands
+    }
+
+    /// Keeps track of what the loops are doing, stopping recordings once they reach their length
+    fn follow_loops(&mut self, view: &loops::View) -> Vec<Command> {
+        let now = Instant::now();
+        let bar = self.bar();
+        let mut commands = vec![];
+        for (n, slot) in self.loops.iter_mut().enumerate() {
+            let recording = view.recording_loop == Some(n);
+            match (slot.is_recording(), recording) {
+                (false, true) => slot.start_recording(now),
+                (true, false) => slot.stop_recording(now),
+                _ => (),
+            }
+            slot.follow_playback(view.playing_loops.get(&n) == Some(&true), now);
+            if slot.stop_if_due(bar, now) {
+                commands.push(Command::Loop(loops::Command::ToggleRecording(n)));
+            }
+        }
+        commands
+    }
+
+    /// Sets up each section of the song as it's reached: its loops, patch, tempo and key.
+    /// The loops playing are stopped once the song is over.
+    fn follow_song(&mut self) -> Vec<Command> {
+        let finished = self.song.tick(&self.clock, self.metronome.beats_per_bar);
+        let position = self.song.position().map(|(section, _)| section);
+        let loops_to = |playing: &dyn Fn(usize) -> bool, loops: &[slots::Slot]| -> Vec<Command> {
+            loops.iter().enumerate()
+                .filter(|(n, slot)| slot.is_playing() != playing(*n))
+                .map(|(n, _)| Command::Loop(loops::Command::TogglePlayback(n)))
+                .collect()
+        };
+        if finished {
+            return loops_to(&|_| false, &self.loops)
+        }
+        let section = match self.song.enter(position) {
+            Some(section) => section.clone(),
+            None => return vec![],
+        };
+        let mut commands = loops_to(&|n| section.loops[n], &self.loops);
+        if section.patch != self.played_patch {
+            self.played_patch = section.patch;
+            commands.extend(editing::update_specs(self));
+        }
+        if let Some(bpm) = section.bpm {
+            self.tapper.set_bpm(bpm);
+        }
+        if let Some(key) = section.key {
+            let shift = (song::key_index(key) as i32 - song::key_index(self.transposed_key) as i32 + 5)
+                .rem_euclid(12) - 5;
+            if shift != 0 {
+                commands.push(Command::Transposer(transposer::Command::TransposeKey(shift as Semitones)));
+            }
+        }
+        commands
+    }
+
+    fn follow_tempo(&mut self, beat: Duration) -> Vec<Command> {
+        if self.beat == Some(beat) {
+            return vec![]
+        }
+        self.beat = Some(beat);
+        if self.modulation.is_synced() {
+            editing::update_specs(self)
+        } else {
+            vec![]
+        }
+    }
+
+    /// Instrument as the synth should play it, with the modulation it's able to follow
+    fn patch(&self) -> instrument::Specs {
+        let patch = &self.patches[self.played_patch];
+        instrument::Specs {
+            volume: patch.instrument.volume * patch.level,
+            lfo: self.modulation.lfo_specs(self.beat),
+            modulation_x: self.modulation.mouse_target(Axis::X),
+            modulation_y: self.modulation.mouse_target(Axis::Y),
+            ..patch.instrument.clone()
+        }
+    }
+
+    /// Period of the pulse, once the synth's view has shown it
+    pub fn beat(&self) -> Option<Duration> {
+        self.beat
+    }
+
+    /// Tempo the tempo keys change, the one being tapped or else the synth's
+    pub fn bpm(&self) -> Option<f64> {
+        self.tapper.target().or_else(|| self.beat.map(tempo::bpm))
+    }
+
+    pub fn bar(&self) -> Option<Duration> {
+        self.beat.map(|beat| beat * self.metronome.beats_per_bar)
+    }
+
+    /// Pitch a note key plays in the scale or tuning, and the row it's on
+    pub fn pitches(&self, key: Key) -> Option<(Pitch, Discriminator)> {
+        let (pitch, discr) = keymap::pitches(key)?;
+        match &self.tuning {
+            Some(tuning) => tuning.key_pitch(pitch, self.config.offset_cents()).map(|(pitch, _)| (pitch, discr)),
+            None => {
+                let pitch = scales::to_midi(self.scale.apply(pitch)) + self.config.semitones();
+                Some((scales::from_midi(pitch), discr))
+            },
+        }
+    }
+
+    /// How far the tuning puts a note key from the pitch the synth plays, when there's one
+    pub fn cents_off(&self, key: Key) -> Option<f64> {
+        let (pitch, _) = keymap::pitches(key)?;
+        self.tuning.as_ref()?.key_pitch(pitch, self.config.offset_cents()).map(|(_, cents)| cents)
+    }
+
+    /// Saves the config once a change is done with, rather than on every step of a drag
+    pub fn save_config(&mut self) {
+        if self.config == self.saved_config {
+            return
+        }
+        match self.config.save() {
+            Ok(()) => self.saved_config = self.config.clone(),
+            Err(e) => self.tuning_status = Some(e.to_string()),
+        }
+    }
+
+    pub fn is_shift_held(&self) -> bool {
+        self.pressed_keys.contains(&Key::LShift) || self.pressed_keys.contains(&Key::RShift)
+    }
+
+    pub fn is_ctrl_held(&self) -> bool {
+        self.pressed_keys.contains(&Key::LCtrl) || self.pressed_keys.contains(&Key::RCtrl)
+    }
+
+    /// Instrument of the patch being edited
+    fn instrument(&self) -> &instrument::Specs {
+        &self.patches[self.selected_patch].instrument
+    }
+
+    fn instrument_mut(&mut self) -> &mut instrument::Specs {
+        &mut self.patches[self.selected_patch].instrument
+    }
+
+    fn track_input(&mut self, input: &Input) {
+        match input {
+            Input::Button(ButtonArgs { state, button: Button::Keyboard(key), .. }) => {
+                match state {
+                    ButtonState::Press => self.pressed_keys.insert(*key),
+                    ButtonState::Release => self.pressed_keys.remove(key),
+                };
+            },
+            Input::Move(MouseCursor(x, y)) => self.cursor = [*x, *y],
+            _ => (),
         }
     }
 
@@ -38,6 +346,14 @@ pub enum EditTarget {
     Filter,
     Arpeggiator,
     LFO,
+    Matrix,
+    Envelope,
+    MouseXY,
+    Patches,
+    Loops,
+    Tempo,
+    Scale,
+    Voices,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -0,0 +1,171 @@
+use std::time::Duration;
+use rust_synth::core::synth::{oscillator, filter, lfo, instrument::ModTarget};
+use rust_synth::core::music_theory::Hz;
+use crate::control::{tempo::Division, mapping::Curve};
+
+pub const N_LFOS: usize = 4;
+pub const MAX_ROUTES: usize = 5;
+
+/// Where a modulation comes from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Source {
+    Lfo(usize),
+}
+
+/// What a modulation changes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Destination {
+    Volume, Cutoff, Resonance, PulseDuty,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lfo {
+    pub oscillator: oscillator::Specs,
+    pub freq: Hz,
+    /// Note division followed instead of `freq`
+    pub sync: Option<Division>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Route {
+    pub source: Source,
+    pub destination: Destination,
+    pub amount: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    X, Y
+}
+
+/// What a mouse axis modulates in playing mode, with the curve its position is mapped through
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MouseRoute {
+    pub destination: Destination,
+    pub curve: Curve,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    pub lfos: Vec<Lfo>,
+    /// Indexed by `Axis`
+    pub mouse: [MouseRoute; 2],
+    pub routes: Vec<Route>,
+}
+
+impl Source {
+    pub const ALL: [Source; N_LFOS] = [Source::Lfo(0), Source::Lfo(1), Source::Lfo(2), Source::Lfo(3)];
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|s| *s == self).unwrap_or(0)
+    }
+}
+
+impl Destination {
+    pub const ALL: [Destination; 4] = [Destination::Volume, Destination::Cutoff, Destination::Resonance,
+                                       Destination::PulseDuty];
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|d| *d == self).unwrap_or(0)
+    }
+
+    /// The synth's equivalent
+    pub fn mod_target(self) -> ModTarget {
+        use Destination::*;
+        match self {
+            Volume => ModTarget::Volume,
+            Cutoff => ModTarget::Filter(filter::ModTarget::Cutoff),
+            Resonance => ModTarget::Filter(filter::ModTarget::QFactor),
+            PulseDuty => ModTarget::Oscillator(oscillator::ModTarget::PulseDuty),
+        }
+    }
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        let specs = lfo::Specs::default();
+        Lfo { oscillator: specs.oscillator, freq: specs.freq, sync: None }
+    }
+}
+
+impl Lfo {
+    pub fn freq(&self, beat: Option<Duration>) -> Hz {
+        match (self.sync, beat) {
+            (Some(division), Some(beat)) => division.freq(beat),
+            _ => self.freq,
+        }
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        let linear = Curve::Linear { min: 0., max: 1. };
+        Matrix {
+            lfos: vec![Lfo::default(); N_LFOS],
+            mouse: [MouseRoute { destination: Destination::Cutoff, curve: linear },
+                    MouseRoute { destination: Destination::Resonance, curve: linear }],
+            routes: vec![],
+        }
+    }
+}
+
+impl Matrix {
+
+    /// The synth runs a single LFO with one target, which plays the first route
+    pub fn played_route(&self) -> Option<usize> {
+        if self.routes.is_empty() { None } else { Some(0) }
+    }
+
+    /// Why a route isn't played by the synth, none if it is
+    pub fn inactive_reason(&self, index: usize) -> Option<&'static str> {
+        self.routes.get(index)?;
+        if self.played_route() != Some(index) { Some("inactive: only the first route plays") } else { None }
+    }
+
+    pub fn played_lfo(&self) -> Option<&Lfo> {
+        let Source::Lfo(n) = self.routes[self.played_route()?].source;
+        self.lfos.get(n)
+    }
+
+    pub fn lfo_specs(&self, beat: Option<Duration>) -> Option<lfo::Specs> {
+        let route = self.routes[self.played_route()?];
+        let lfo = self.played_lfo()?;
+        Some(lfo::Specs {
+            freq: lfo.freq(beat),
+            amount: route.amount,
+            target: route.destination.mod_target(),
+            oscillator: lfo.oscillator.clone(),
+        })
+    }
+
+    /// First route from a source, the one edited in the LFO page
+    pub fn route_from(&self, source: Source) -> Option<usize> {
+        self.routes.iter().position(|route| route.source == source)
+    }
+
+    pub fn add_route(&mut self, route: Route) -> Option<usize> {
+        if self.routes.len() < MAX_ROUTES {
+            self.routes.push(route);
+            Some(self.routes.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn mouse_route(&self, axis: Axis) -> &MouseRoute {
+        &self.mouse[axis as usize]
+    }
+
+    pub fn mouse_route_mut(&mut self, axis: Axis) -> &mut MouseRoute {
+        &mut self.mouse[axis as usize]
+    }
+
+    /// Synth's target for a mouse axis
+    pub fn mouse_target(&self, axis: Axis) -> ModTarget {
+        self.mouse_route(axis).destination.mod_target()
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lfos.iter().any(|lfo| lfo.sync.is_some())
+    }
+}
@@ -0,0 +1,443 @@
+use rust_synth::core::synth::{oscillator, filter, adsr};
+use rust_synth::core::tools::arpeggiator;
+use rust_synth::core::music_theory::diatonic_scale::{self, OctaveShift};
+use crate::control::{Control, Mode, EditTarget, OscillatorTarget, mapping::{Curve, SHAPES}, tempo::{self, DIVISIONS},
+                     modulation::{Lfo, Route, Source, Destination, Axis, N_LFOS},
+                     editing::MAX_SEED, slots::{Slot, N_LOOPS, MAX_BARS},
+                     quantize::GRIDS, metronome::{MIN_BEATS_PER_BAR, MAX_BEATS_PER_BAR}, scales, config,
+                     voices::{Allocation, Stealing, Priority, MAX_VOICES}};
+
+/// A single value of the patch that can be edited with a widget
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Param {
+    Waveform, DutyCycle, MixVoices, MixDetune, MixWaveform, MixSeed, MixSeedLock,
+    FilterType, FilterCutoff, FilterResonance,
+    ArpOn, ArpChord, ArpDirection, ArpOctaveMin, ArpOctaveMax,
+    LfoSelect, LfoOn, LfoTarget, LfoWaveform, LfoAmount, LfoFreq, LfoSync, LfoDivision,
+    RouteSource(usize), RouteDestination(usize), RouteAmount(usize),
+    EnvAttack, EnvDecay, EnvSustain, EnvRelease,
+    MouseDestination(Axis), MouseCurve(Axis), MouseMin(Axis), MouseMax(Axis),
+    PatchSelect, PatchLevel,
+    LoopSelect, LoopBars,
+    Quantize, Swing, Metronome, CountIn, BeatsPerBar,
+    ScaleSelect, ScaleNote(usize), Reference,
+    VoiceAllocation, VoiceLimit, VoiceStealing, VoicePriority,
+}
+
+const DESTINATIONS: [&str; 4] = ["volume", "cutoff", "resonance", "pulse duty"];
+
+impl Param {
+
+    /// Choices for params edited with a dropdown, empty for the others
+    pub fn options(self) -> &'static [&'static str] {
+        use Param::*;
+        match self {
+            Waveform => &["sine", "saw", "square", "pulse", "mix"],
+            MixWaveform | LfoWaveform => &["sine", "saw", "square"],
+            FilterType => &["LPF", "HPF", "BPF", "Notch"],
+            ArpChord => &["octaves", "triad", "fantasy", "tetra", "penta"],
+            ArpDirection => &["up", "down", "up-down"],
+            LfoTarget | RouteDestination(_) | MouseDestination(_) => &DESTINATIONS,
+            MouseCurve(_) => &SHAPES,
+            RouteSource(_) => &["lfo 1", "lfo 2", "lfo 3", "lfo 4"],
+            PatchSelect => &["patch 1", "patch 2"],
+            Quantize => &["off", "1/8", "1/16", "1/8t", "1/16t"],
+            ScaleSelect => &scales::NAMES,
+            VoiceAllocation => &["poly", "mono"],
+            VoiceStealing => &["oldest", "lowest"],
+            VoicePriority => &["last", "low", "high"],
+            _ => &[],
+        }
+    }
+
+    pub fn curve(self) -> Curve {
+        use {Param::*, Curve::*};
+        match self {
+            MixVoices => Stepped { min: 1., max: 40. },
+            MixDetune => Exponential { min: 0.001, max: 32. },
+            MixSeed => Stepped { min: 0., max: MAX_SEED as f64 },
+            FilterCutoff => Exponential { min: 0., max: 1. },
+            FilterResonance => Logarithmic { min: 0., max: 1. },
+            ArpOn | LfoOn | LfoSync | Metronome | CountIn | ScaleNote(_) | MixSeedLock =>
+                Stepped { min: 0., max: 1. },
+            ArpOctaveMin | ArpOctaveMax =>
+                Stepped { min: OctaveShift::Down3 as i8 as f64, max: OctaveShift::Up3 as i8 as f64 },
+            LfoSelect => Stepped { min: 0., max: (N_LFOS - 1) as f64 },
+            LfoFreq => Exponential { min: 0., max: 55. },
+            LfoDivision => TempoSynced,
+            LoopSelect => Stepped { min: 0., max: (N_LOOPS - 1) as f64 },
+            LoopBars => Stepped { min: 0., max: MAX_BARS as f64 },
+            Swing => Linear { min: 0.5, max: 0.75 },
+            BeatsPerBar => Stepped { min: MIN_BEATS_PER_BAR as f64, max: MAX_BEATS_PER_BAR as f64 },
+            Reference => Stepped { min: -config::MAX_SEMITONES as f64, max: config::MAX_SEMITONES as f64 },
+            VoiceLimit => Stepped { min: 1., max: MAX_VOICES as f64 },
+            EnvAttack | EnvDecay | EnvRelease => Exponential { min: 0., max: 5. },
+            _ => Linear { min: 0., max: 1. },
+        }
+    }
+}
+
+impl Control {
+
+    /// Value of a knob, slider or toggle, normalized between 0 and 1 along the param's curve
+    pub fn value(&self, param: Param) -> f64 {
+        self.get(param).map_or(0., |value| param.curve().to_normalized(value))
+    }
+
+    pub fn set_value(&mut self, param: Param, normalized: f64) {
+        self.set(param, param.curve().to_value(normalized));
+    }
+
+    /// Value of a knob or slider as shown next to it
+    pub fn display(&self, param: Param) -> String {
+        match (param, self.get(param)) {
+            (Param::LfoDivision, Some(index)) => DIVISIONS[index as usize].to_string(),
+            (Param::LfoSelect, Some(index)) | (Param::LoopSelect, Some(index)) => format!("{}", index as usize + 1),
+            (Param::LoopBars, Some(bars)) if bars < 1. => "free".to_string(),
+            (Param::Swing, Some(swing)) => format!("{:.0}%", swing * 100.),
+            (Param::BeatsPerBar, Some(beats)) => format!("{:.0}/4", beats),
+            (Param::Reference, Some(semitones)) => format!("{:.0} Hz", config::reference(semitones as i32)),
+            (_, Some(value)) if param.curve().is_stepped() => format!("{:.0}", value),
+            (_, Some(value)) => format!("{:.2}", value),
+            (_, None) => String::new(),
+        }
+    }
+
+    /// Index of the current choice among `param.options()`
+    pub fn selected(&self, param: Param) -> usize {
+        use Param::*;
+        use oscillator::{Specs, Basic};
+        use arpeggiator::builder::{Chord, Direction};
+        let basic_index = |basic: &Basic| match basic {
+            Basic::Sine => 0,
+            Basic::Saw => 1,
+            Basic::Square => 2,
+        };
+        match param {
+            Waveform => match &self.instrument().oscillator {
+                Specs::Basic(basic) => basic_index(basic),
+                Specs::Pulse(_) => 3,
+                Specs::Mix { .. } => 4,
+            },
+            FilterType => {
+                use filter::TypeSpec::*;
+                match self.instrument().filter.filter_type {
+                    LPF => 0,
+                    HPF => 1,
+                    BPF => 2,
+                    Notch => 3,
+                }
+            },
+            ArpChord => self.arpeggiator.as_ref().map_or(0, |arp| match arp.phrase.chord {
+                Chord::Octaves => 0,
+                Chord::Triad => 1,
+                Chord::Fantasy => 2,
+                Chord::Tetra => 3,
+                Chord::Penta => 4,
+            }),
+            ArpDirection => self.arpeggiator.as_ref().map_or(0, |arp| match arp.phrase.direction {
+                Direction::Up => 0,
+                Direction::Down => 1,
+                Direction::UpDown => 2,
+            }),
+            LfoTarget => self.lfo_route().map_or(0, |route| route.destination.index()),
+            MixWaveform => match &self.instrument().oscillator {
+                Specs::Mix { specs, .. } => basic_index(specs),
+                _ => 0,
+            },
+            LfoWaveform => match &self.lfo().oscillator {
+                Specs::Basic(basic) => basic_index(basic),
+                _ => 0,
+            },
+            RouteSource(i) => self.modulation.routes.get(i).map_or(0, |route| route.source.index()),
+            RouteDestination(i) => self.modulation.routes.get(i).map_or(0, |route| route.destination.index()),
+            MouseDestination(axis) => self.modulation.mouse_route(axis).destination.index(),
+            MouseCurve(axis) => self.modulation.mouse_route(axis).curve.shape(),
+            PatchSelect => self.selected_patch,
+            Quantize => GRIDS.iter().position(|grid| *grid == self.quantizer.grid).unwrap_or(0),
+            ScaleSelect => self.scale.selected,
+            VoiceAllocation => match self.voices.allocation {
+                Allocation::Poly => 0,
+                Allocation::Mono => 1,
+            },
+            VoiceStealing => match self.voices.stealing {
+                Stealing::Oldest => 0,
+                Stealing::Lowest => 1,
+            },
+            VoicePriority => match self.voices.priority {
+                Priority::Last => 0,
+                Priority::Low => 1,
+                Priority::High => 2,
+            },
+            _ => 0,
+        }
+    }
+
+    pub fn select(&mut self, param: Param, index: usize) {
+        use Param::*;
+        use oscillator::{Specs, Basic};
+        use arpeggiator::builder::{Chord, Direction};
+        let basic = |index: usize| match index {
+            0 => Basic::Sine,
+            1 => Basic::Saw,
+            _ => Basic::Square,
+        };
+        match param {
+            Waveform => {
+                let (oscillator, target) = match index {
+                    3 => (Specs::Pulse(0.5), Some(OscillatorTarget::Pulse)),
+                    4 => (self.patches[self.selected_patch].mix(), Some(OscillatorTarget::Mix)),
+                    _ => (Specs::Basic(basic(index)), None),
+                };
+                if self.selected(Waveform) != index {
+                    self.patches[self.selected_patch].set_oscillator(oscillator);
+                }
+                if let Mode::Editing(Some(EditTarget::Oscillator(_))) = self.mode {
+                    self.mode = Mode::Editing(Some(EditTarget::Oscillator(target)));
+                }
+            },
+            FilterType => {
+                use filter::TypeSpec::*;
+                self.instrument_mut().filter.filter_type = match index {
+                    0 => LPF,
+                    1 => HPF,
+                    2 => BPF,
+                    _ => Notch,
+                };
+            },
+            ArpChord => {
+                self.arpeggiator_or_default().phrase.chord = match index {
+                    0 => Chord::Octaves,
+                    1 => Chord::Triad,
+                    2 => Chord::Fantasy,
+                    3 => Chord::Tetra,
+                    _ => Chord::Penta,
+                };
+            },
+            ArpDirection => {
+                self.arpeggiator_or_default().phrase.direction = match index {
+                    0 => Direction::Up,
+                    1 => Direction::Down,
+                    _ => Direction::UpDown,
+                };
+            },
+            LfoTarget => if let Some(route) = self.lfo_route_or_default() {
+                route.destination = Destination::ALL[index];
+            },
+            MixWaveform => if let Specs::Mix { specs, .. } = &mut self.instrument_mut().oscillator {
+                *specs = basic(index);
+            },
+            LfoWaveform => self.lfo_mut().oscillator = Specs::Basic(basic(index)),
+            RouteSource(i) => if let Some(route) = self.modulation.routes.get_mut(i) {
+                route.source = Source::ALL[index];
+            },
+            RouteDestination(i) => if let Some(route) = self.modulation.routes.get_mut(i) {
+                route.destination = Destination::ALL[index];
+            },
+            MouseDestination(axis) => self.modulation.mouse_route_mut(axis).destination = Destination::ALL[index],
+            MouseCurve(axis) => {
+                let route = self.modulation.mouse_route_mut(axis);
+                let (min, max) = route.curve.range();
+                route.curve = Curve::with_shape(index, min, max);
+            },
+            PatchSelect => {
+                self.selected_patch = index;
+                self.played_patch = index;
+            },
+            Quantize => self.quantizer.grid = GRIDS[index],
+            ScaleSelect => self.scale.selected = index,
+            VoiceAllocation => self.voices.allocation = match index {
+                0 => Allocation::Poly,
+                _ => Allocation::Mono,
+            },
+            VoiceStealing => self.voices.stealing = match index {
+                0 => Stealing::Oldest,
+                _ => Stealing::Lowest,
+            },
+            VoicePriority => self.voices.priority = match index {
+                0 => Priority::Last,
+                1 => Priority::Low,
+                _ => Priority::High,
+            },
+            _ => (),
+        }
+    }
+
+    fn get(&self, param: Param) -> Option<f64> {
+        use {Param::*, oscillator::Specs};
+        match param {
+            DutyCycle => match self.instrument().oscillator {
+                Specs::Pulse(duty) => Some(duty),
+                _ => None,
+            },
+            MixVoices => match self.instrument().oscillator {
+                Specs::Mix { n_voices, .. } => Some(n_voices as f64),
+                _ => None,
+            },
+            MixDetune => match self.instrument().oscillator {
+                Specs::Mix { detune_amount, .. } => Some(detune_amount),
+                _ => None,
+            },
+            MixSeed => match self.instrument().oscillator {
+                Specs::Mix { random_seed, .. } => Some(random_seed as f64),
+                _ => None,
+            },
+            MixSeedLock => Some(bool_value(self.patches[self.selected_patch].seed_locked)),
+            FilterCutoff => Some(self.instrument().filter.cutoff),
+            FilterResonance => Some(self.instrument().filter.resonance),
+            ArpOn => Some(bool_value(self.arpeggiator.is_some())),
+            ArpOctaveMin => self.arpeggiator.as_ref().map(|arp| arp.phrase.octave_min as i8 as f64),
+            ArpOctaveMax => self.arpeggiator.as_ref().map(|arp| arp.phrase.octave_max as i8 as f64),
+            LfoSelect => Some(self.selected_lfo as f64),
+            LfoOn => Some(bool_value(self.lfo_route().is_some())),
+            LfoAmount => self.lfo_route().map(|route| route.amount),
+            LfoFreq => Some(self.lfo().freq),
+            LfoSync => Some(bool_value(self.lfo().sync.is_some())),
+            LfoDivision => self.lfo().sync.map(|division| division.index() as f64),
+            RouteAmount(i) => self.modulation.routes.get(i).map(|route| route.amount),
+            EnvAttack => Some(self.envelope().attack),
+            EnvDecay => Some(self.envelope().decay),
+            EnvSustain => Some(self.envelope().sustain),
+            EnvRelease => Some(self.envelope().release),
+            MouseMin(axis) => Some(self.modulation.mouse_route(axis).curve.range().0),
+            MouseMax(axis) => Some(self.modulation.mouse_route(axis).curve.range().1),
+            PatchLevel => Some(self.patches[self.selected_patch].level),
+            LoopSelect => Some(self.selected_loop as f64),
+            LoopBars => Some(self.loop_slot().bars.map_or(0., |bars| bars as f64)),
+            Swing => Some(self.quantizer.swing),
+            Metronome => Some(bool_value(self.metronome.on)),
+            CountIn => Some(bool_value(self.metronome.count_in)),
+            BeatsPerBar => Some(self.metronome.beats_per_bar as f64),
+            ScaleNote(semitone) => Some(bool_value(self.scale.custom[semitone])),
+            Reference => Some(self.config.semitones() as f64),
+            VoiceLimit => Some(self.voices.limit as f64),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, param: Param, value: f64) {
+        use {Param::*, oscillator::Specs};
+        match param {
+            DutyCycle => if let Specs::Pulse(duty) = &mut self.instrument_mut().oscillator {
+                *duty = value;
+            },
+            MixVoices => if let Specs::Mix { n_voices, .. } = &mut self.instrument_mut().oscillator {
+                *n_voices = value.round() as usize;
+            },
+            MixDetune => if let Specs::Mix { detune_amount, .. } = &mut self.instrument_mut().oscillator {
+                *detune_amount = value;
+            },
+            MixSeed => if let Specs::Mix { random_seed, .. } = &mut self.instrument_mut().oscillator {
+                *random_seed = value as u64;
+            },
+            MixSeedLock => self.patches[self.selected_patch].seed_locked = value >= 0.5,
+            FilterCutoff => self.instrument_mut().filter.cutoff = value,
+            FilterResonance => self.instrument_mut().filter.resonance = value,
+            ArpOn => match (value >= 0.5, self.arpeggiator.is_some()) {
+                (true, false) => { self.arpeggiator_or_default(); },
+                (false, true) => self.arpeggiator = None,
+                _ => (),
+            },
+            ArpOctaveMin | ArpOctaveMax =>
+                if let (Some(arp), Some(octave)) = (&mut self.arpeggiator, octave_shift(value)) {
+                    if param == ArpOctaveMin {
+                        arp.phrase.octave_min = octave;
+                        if (arp.phrase.octave_max as i8) < octave as i8 { arp.phrase.octave_max = octave }
+                    } else {
+                        arp.phrase.octave_max = octave;
+                        if (arp.phrase.octave_min as i8) > octave as i8 { arp.phrase.octave_min = octave }
+                    }
+                },
+            LfoSelect => self.selected_lfo = value as usize,
+            LfoOn => if value >= 0.5 {
+                self.lfo_route_or_default();
+            } else {
+                let source = Source::Lfo(self.selected_lfo);
+                self.modulation.routes.retain(|route| route.source != source);
+            },
+            LfoAmount => if let Some(route) = self.lfo_route_or_default() { route.amount = value },
+            LfoFreq => self.lfo_mut().freq = value,
+            LfoSync => {
+                let lfo = self.lfo_mut();
+                lfo.sync = if value >= 0.5 { lfo.sync.or(Some(tempo::QUARTER)) } else { None };
+            },
+            LfoDivision => self.lfo_mut().sync = Some(DIVISIONS[value as usize]),
+            RouteAmount(i) => if let Some(route) = self.modulation.routes.get_mut(i) { route.amount = value },
+            EnvAttack => self.envelope_mut().attack = value,
+            EnvDecay => self.envelope_mut().decay = value,
+            EnvSustain => self.envelope_mut().sustain = value,
+            EnvRelease => self.envelope_mut().release = value,
+            MouseMin(axis) | MouseMax(axis) => {
+                let route = self.modulation.mouse_route_mut(axis);
+                let (min, max) = route.curve.range();
+                let (min, max) = if let MouseMin(_) = param { (value, max) } else { (min, value) };
+                route.curve = Curve::with_shape(route.curve.shape(), min, max);
+            },
+            PatchLevel => self.patches[self.selected_patch].level = value,
+            LoopSelect => self.selected_loop = value as usize,
+            LoopBars => self.loop_slot_mut().bars = if value >= 1. { Some(value as u32) } else { None },
+            Swing => self.quantizer.swing = value,
+            Metronome => self.metronome.on = value >= 0.5,
+            CountIn => self.metronome.count_in = value >= 0.5,
+            BeatsPerBar => self.metronome.beats_per_bar = value as u32,
+            ScaleNote(semitone) => self.scale.custom[semitone] = value >= 0.5,
+            VoiceLimit => self.voices.limit = value as usize,
+            Reference => self.config.reference = config::reference(value as i32),
+            _ => (),
+        }
+    }
+
+    fn arpeggiator_or_default(&mut self) -> &mut arpeggiator::Specs {
+        self.arpeggiator.get_or_insert_with(|| arpeggiator::Specs {
+            key: diatonic_scale::Key::C,
+            phrase: Default::default(),
+        })
+    }
+
+    fn lfo(&self) -> &Lfo {
+        &self.modulation.lfos[self.selected_lfo]
+    }
+
+    fn lfo_mut(&mut self) -> &mut Lfo {
+        &mut self.modulation.lfos[self.selected_lfo]
+    }
+
+    fn loop_slot(&self) -> &Slot {
+        &self.loops[self.selected_loop]
+    }
+
+    fn loop_slot_mut(&mut self) -> &mut Slot {
+        &mut self.loops[self.selected_loop]
+    }
+
+    /// Envelope shown in the envelope page, the instrument's own
+    pub fn envelope(&self) -> &adsr::Specs {
+        &self.instrument().adsr
+    }
+
+    fn envelope_mut(&mut self) -> &mut adsr::Specs {
+        &mut self.instrument_mut().adsr
+    }
+
+    /// Route from the LFO being edited, telling what it modulates and by how much
+    fn lfo_route(&self) -> Option<&Route> {
+        let index = self.modulation.route_from(Source::Lfo(self.selected_lfo))?;
+        self.modulation.routes.get(index)
+    }
+
+    fn lfo_route_or_default(&mut self) -> Option<&mut Route> {
+        let source = Source::Lfo(self.selected_lfo);
+        let index = self.modulation.route_from(source)
+            .or_else(|| self.modulation.add_route(Route { source, destination: Destination::Cutoff, amount: 1. }))?;
+        self.modulation.routes.get_mut(index)
+    }
+}
+
+/// Octave shift nearest to a value, none when it's out of the arpeggiator's range
+fn octave_shift(value: f64) -> Option<OctaveShift> {
+    OctaveShift::from_i8(value.round() as i8)
+}
+
+fn bool_value(b: bool) -> f64 {
+    if b { 1. } else { 0. }
+}
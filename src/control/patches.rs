@@ -0,0 +1,41 @@
+use rust_synth::core::synth::{instrument, oscillator};
+
+pub const N_PATCHES: usize = 2;
+const DEFAULT_MIX: oscillator::Specs =
+    oscillator::Specs::Mix { n_voices: 8, detune_amount: 3., specs: oscillator::Basic::Saw, random_seed: 0 };
+
+/// One of the patches the note keys switch between, as the synth plays one at a time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    pub instrument: instrument::Specs,
+    /// Scales the instrument's volume, to balance it against the other patch
+    pub level: f64,
+    /// Keeps the detuned mix's random seed when its key is pressed again
+    pub seed_locked: bool,
+    /// Detuned mix last switched away from, so that its waveform and seed come back with it
+    last_mix: Option<oscillator::Specs>,
+}
+
+impl Default for Patch {
+    fn default() -> Self {
+        Patch { instrument: Default::default(), level: 1., seed_locked: false, last_mix: None }
+    }
+}
+
+impl Patch {
+
+    pub fn set_oscillator(&mut self, specs: oscillator::Specs) {
+        if let oscillator::Specs::Mix { .. } = self.instrument.oscillator {
+            self.last_mix = Some(self.instrument.oscillator.clone());
+        }
+        self.instrument.oscillator = specs;
+    }
+
+    /// Detuned mix playing, or the one last played, or else a new one
+    pub fn mix(&self) -> oscillator::Specs {
+        match &self.instrument.oscillator {
+            mix @ oscillator::Specs::Mix { .. } => mix.clone(),
+            _ => self.last_mix.clone().unwrap_or(DEFAULT_MIX),
+        }
+    }
+}
@@ -1,96 +1,120 @@
+use std::time::Instant;
 use piston_window::{Button::*, ButtonArgs, ButtonState::*, Input, Input::*, Key, Motion, Motion::*};
 use rust_synth::core::{
-    control::{synth::{Command::*, Discriminator, id_discr},tools::Command::{self, *}},
+    control::{synth::{Command::*, id_discr},tools::Command::{self, *}},
     tools::{transposer::Command::*, loops::Command::*},
-    music_theory::{pitch::Pitch, pitch_class::PitchClass::*},
+    music_theory::Semitones,
 };
-use crate::control::{Control, Mode};
+use crate::control::{Control, Mode, keymap::pitches, modulation::Axis, };
+
+/// How far the step keys, or the mouse at the window's edges, transpose the held notes
+const STEP_RANGE: Semitones = 2;
 
 pub fn handle_input(input: &Input, window_size: [f64;2], control: &mut Control) -> Vec<Command> {
     match input {
         Button(args) => handle_button(args, control),
-        Move(args) => handle_move(args, window_size),
+        Move(args) => handle_move(args, window_size, control),
         _ => vec![],
     }
 }
 
 fn handle_button(args: &ButtonArgs, control: &mut Control) -> Vec<Command> { //TODO Option<Command> ?
     match (args.state, args.button) {
+        (state, Keyboard(key)) if step_direction(key).is_some() => {
+            let semitones = if state == Press { step_direction(key).unwrap_or(0) * STEP_RANGE } else { 0 };
+            set_step_transpose(semitones, control)
+        },
+        (Release, Keyboard(Key::Backslash)) => {
+            control.mouse_transposes = !control.mouse_transposes;
+            set_step_transpose(0, control)
+        },
+        (Release, Keyboard(Key::Return)) => {
+            control.xy_frozen = !control.xy_frozen;
+            vec![]
+        },
+        (Press, Keyboard(key)) if recorded_loop(key).is_some() =>
+            recorded_loop(key).map_or(vec![], |n| record(n, control)),
+        (Press, Keyboard(Key::Space)) => {
+            control.clock.tap();
+            control.tapper.tap(Instant::now());
+            vec![TapTempo]
+        },
+        (Press, Keyboard(key)) if control.is_ctrl_held() && is_tempo_key(key) => {
+            change_tempo(key, control);
+            vec![]
+        },
+        (Press, Keyboard(key)) if pitches(key).is_some() => {
+            note_on(key, control)
+        },
+        (Release, Keyboard(key)) if pitches(key).is_some() => note_off(key, control),
         (Press, Keyboard(key))   =>
-            note_on(key)
-                .or_else(|| loop_rec(key))
-                .or_else(|| tap_tempo(key))
+            loop_rec(key)
                 .or_else(|| transpose(key))
                 .map_or(vec![], |v| vec![v]),
         (Release, Keyboard(key)) =>
-            note_off(key)
-                .or_else(|| mode(key, control))
+            mode(key, control)
                 .map_or(vec![], |v| vec![v]),
         _ => vec![],
     }
 }
 
-fn handle_move(motion: &Motion, window_size: [f64;2]) -> Vec<Command> {
+fn handle_move(motion: &Motion, window_size: [f64;2], control: &mut Control) -> Vec<Command> {
     match motion {
+        MouseCursor(_, y) if control.mouse_transposes => {
+            let norm_y = 1. - 2. * y / window_size[1];
+            let semitones = (norm_y * STEP_RANGE as f64).round() as Semitones;
+            set_step_transpose(semitones, control)
+        }
+        MouseCursor(_, _) if control.xy_frozen => vec![],
         MouseCursor(x, y) => {
             let norm_x = x / window_size[0] as f64;
             let norm_y = y / window_size[1] as f64;
-            let command = Instrument(ModXY(norm_x, norm_y));
+            control.mouse_xy = [norm_x, norm_y];
+            let mod_x = control.modulation.mouse_route(Axis::X).curve.to_value(norm_x);
+            let mod_y = control.modulation.mouse_route(Axis::Y).curve.to_value(norm_y);
+            let command = Instrument(ModXY(mod_x, mod_y));
             vec![command]
         }
         _ => vec![],
     }
 }
 
-fn note_on(key: Key) -> Option<Command> {
-    pitches(key).map(|(pitch, discr)|
-        Instrument(NoteOn(pitch, 1., id_discr(pitch, discr))))
+fn step_direction(key: Key) -> Option<Semitones> {
+    match key {
+        Key::D9 => Some(-1),
+        Key::D0 => Some(1),
+        _ => None,
+    }
 }
 
-fn note_off(key: Key) -> Option<Command> {
-    pitches(key).map(|(pitch, discr)|
-        Instrument(NoteOff(id_discr(pitch, discr))))
+/// Transposes the held notes in semitone steps, restriking them through the transposer at their own velocity.
+/// The synth applies the transposer to new notes only and has no pitch target to modulate, so it can't bend.
+pub fn set_step_transpose(semitones: Semitones, control: &mut Control) -> Vec<Command> {
+    let shift = semitones - control.step_transpose;
+    if shift == 0 {
+        return vec![]
+    }
+    control.step_transpose = semitones;
+    let mut commands = vec![Transposer(ShiftPitch(shift))];
+    for (id, pitch, velocity) in control.voices.sounding() {
+        commands.push(Instrument(NoteOff(id)));
+        commands.push(Instrument(NoteOn(pitch, velocity, id)));
+    }
+    commands
 }
 
-fn pitches(key: Key) -> Option<(Pitch, Discriminator)> { //TODO shift => sharp pitches
-    match key {
-        //top row
-        Key::Q =>         Some((Pitch::new(A, 4), 3)),
-        Key::W =>         Some((Pitch::new(B, 4), 3)),
-        Key::E =>         Some((Pitch::new(C, 5), 3)),
-        Key::R =>         Some((Pitch::new(D, 5), 3)),
-        Key::T =>         Some((Pitch::new(E, 5), 3)),
-        Key::Y =>         Some((Pitch::new(F, 5), 3)),
-        Key::U =>         Some((Pitch::new(G, 5), 3)),
-        Key::I =>         Some((Pitch::new(A, 5), 3)),
-        Key::O =>         Some((Pitch::new(B, 5), 3)),
-        Key::P =>         Some((Pitch::new(C, 6), 3)),
-
-        //middle row
-        Key::A =>         Some((Pitch::new(A, 3), 2)),
-        Key::S =>         Some((Pitch::new(B, 3), 2)),
-        Key::D =>         Some((Pitch::new(C, 4), 2)),
-        Key::F =>         Some((Pitch::new(D, 4), 2)),
-        Key::G =>         Some((Pitch::new(E, 4), 2)),
-        Key::H =>         Some((Pitch::new(F, 4), 2)),
-        Key::J =>         Some((Pitch::new(G, 4), 2)),
-        Key::K =>         Some((Pitch::new(A, 4), 2)),
-        Key::L =>         Some((Pitch::new(B, 4), 2)),
-        Key::Semicolon => Some((Pitch::new(C, 5), 2)),
-
-        //bottom row
-        Key::Z =>         Some((Pitch::new(A, 2), 1)),
-        Key::X =>         Some((Pitch::new(B, 2), 1)),
-        Key::C =>         Some((Pitch::new(C, 3), 1)),
-        Key::V =>         Some((Pitch::new(D, 3), 1)),
-        Key::B =>         Some((Pitch::new(E, 3), 1)),
-        Key::N =>         Some((Pitch::new(F, 3), 1)),
-        Key::M =>         Some((Pitch::new(G, 3), 1)),
-        Key::Comma =>     Some((Pitch::new(A, 3), 1)),
-        Key::Period =>    Some((Pitch::new(B, 3), 1)),
-        Key::Slash =>     Some((Pitch::new(C, 4), 1)),
+/// Notes go through the voice allocation, which may stop others to make room
+fn note_on(key: Key, control: &mut Control) -> Vec<Command> {
+    match control.pitches(key) {
+        Some((pitch, discr)) => control.voices.note_on(id_discr(pitch, discr), pitch, 1., Instant::now()),
+        None => vec![],
+    }
+}
 
-        _ => None,
+fn note_off(key: Key, control: &mut Control) -> Vec<Command> {
+    match control.pitches(key) {
+        Some((pitch, discr)) => control.voices.note_off(id_discr(pitch, discr)),
+        None => vec![],
     }
 }
 
@@ -110,13 +134,40 @@ fn loop_rec(key: Key) -> Option<Command> {
     }
 }
 
-fn tap_tempo(key: Key) -> Option<Command> {
-    match key {
-        Key::Space =>  Some(TapTempo),
+fn is_tempo_key(key: Key) -> bool {
+    matches!(key, Key::Minus | Key::Equals | Key::LeftBracket | Key::RightBracket)
+}
+
+/// Tempo keys: - and = nudge it by 1 BPM, [ and ] halve and double it
+pub fn change_tempo(key: Key, control: &mut Control) {
+    let bpm = match (control.bpm().map(f64::round), key) {
+        (Some(bpm), Key::Minus) => bpm - 1.,
+        (Some(bpm), Key::Equals) => bpm + 1.,
+        (Some(bpm), Key::LeftBracket) => bpm / 2.,
+        (Some(bpm), Key::RightBracket) => bpm * 2.,
+        _ => return,
+    };
+    control.tapper.set_bpm(bpm);
+}
+
+fn recorded_loop(key: Key) -> Option<usize> {
+    match loop_rec(key) {
+        Some(Loop(ToggleRecording(n))) => Some(n),
         _ => None,
     }
 }
 
+/// Starts or stops recording a loop, starting it after a bar of count-in when it's on
+fn record(n: usize, control: &mut Control) -> Vec<Command> {
+    match control.beat() {
+        Some(_) if control.metronome.count_in && !control.loops[n].is_recording() => {
+            control.metronome.start_count_in(n, &control.clock);
+            vec![]
+        },
+        _ => vec![Loop(ToggleRecording(n))],
+    }
+}
+
 fn transpose(key: Key) -> Option<Command> {
     match key {
         Key::Down =>         Some(Transposer(ShiftPitch(-12))),
@@ -132,8 +183,10 @@ fn transpose(key: Key) -> Option<Command> {
 }
 
 fn mode(key: Key, control: &mut Control) -> Option<Command> {
-    if key == Key::Tab {
-        control.mode = Mode::Editing(None);
+    match key {
+        Key::Tab => control.mode = Mode::Editing(None),
+        Key::Backquote => control.mode = Mode::Sequencing,
+        _ => (),
     }
     None
 }
\ No newline at end of file
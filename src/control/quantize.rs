@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use rust_synth::core::control::{synth, tools::Command};
+use crate::control::tempo::{Division, Feel};
+
+/// Grids notes can be quantized to, none for the first
+pub const GRIDS: [Option<Division>; 5] = [
+    None,
+    Some(Division { denominator: 8, feel: Feel::Straight }),
+    Some(Division { denominator: 16, feel: Feel::Straight }),
+    Some(Division { denominator: 8, feel: Feel::Triplet }),
+    Some(Division { denominator: 16, feel: Feel::Triplet }),
+];
+
+/// Moves the start of notes played while a loop records onto a grid of the tempo.
+/// Notes played early are held back to their step, while the late ones, which can't be sent in the past,
+/// play at once: playing never lags by more than half a step. A note off is held back as long as its note on,
+/// keeping the note's length.
+pub struct Quantizer {
+    pub grid: Option<Division>,
+    /// Where the second step of each pair falls, as a fraction of the pair. Half is straight.
+    pub swing: f64,
+    pending: Vec<(Instant, Command)>,
+    /// How long the note on of each note held back was delayed, for its note off to follow
+    delays: HashMap<synth::Id, Duration>,
+}
+
+impl Default for Quantizer {
+    fn default() -> Self {
+        Quantizer { grid: None, swing: 0.5, pending: vec![], delays: HashMap::new() }
+    }
+}
+
+impl Quantizer {
+
+    /// Commands to send now, holding back early note ons until their step when there's a grid to put them on,
+    /// counted from an origin at a beat, and note offs by as long as their note on was
+    pub fn schedule(&mut self, commands: Vec<Command>, grid: Option<(Instant, Duration)>, now: Instant)
+                    -> Vec<Command> {
+        let mut ready = vec![];
+        for command in commands {
+            let time = match &command {
+                Command::Instrument(synth::Command::NoteOn(_, _, id)) => {
+                    let time = grid.and_then(|(origin, beat)| self.snap(origin, beat, now)).filter(|time| *time > now);
+                    match time {
+                        Some(time) => self.delays.insert(*id, time - now),
+                        None => self.delays.remove(id),
+                    };
+                    time
+                },
+                Command::Instrument(synth::Command::NoteOff(id)) => self.delays.remove(id).map(|delay| now + delay),
+                _ => None,
+            };
+            match time {
+                Some(time) if time > now => self.pending.push((time, command)),
+                _ => ready.push(command),
+            }
+        }
+        ready
+    }
+
+    /// Commands whose time has come
+    pub fn due(&mut self, now: Instant) -> Vec<Command> {
+        let (mut due, pending): (Vec<_>, Vec<_>) = self.pending.drain(..).partition(|(time, _)| *time <= now);
+        self.pending = pending;
+        due.sort_by_key(|(time, _)| *time);
+        due.into_iter().map(|(_, command)| command).collect()
+    }
+
+    /// Nearest step of the grid, counting from the origin, which may have passed already
+    fn snap(&self, origin: Instant, beat: Duration, now: Instant) -> Option<Instant> {
+        let step = self.grid?.beats() * beat.as_secs_f64();
+        let pair = 2. * step;
+        let elapsed = now.saturating_duration_since(origin).as_secs_f64();
+        let pair_start = (elapsed / pair).floor() * pair;
+        let steps = [pair_start, pair_start + pair * self.swing, pair_start + pair];
+        let nearest = steps.iter().copied()
+            .min_by(|a, b| (a - elapsed).abs().total_cmp(&(b - elapsed).abs()))?;
+        Some(origin + Duration::from_secs_f64(nearest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_synth::core::{control::synth::{id_discr, Command::*}, music_theory::pitch::Pitch};
+
+    const BEAT: Duration = Duration::from_secs(1);
+
+    fn eighths(swing: f64) -> Quantizer {
+        Quantizer { grid: GRIDS[1], swing, ..Default::default() }
+    }
+
+    fn at(origin: Instant, secs: f64) -> Instant {
+        origin + Duration::from_secs_f64(secs)
+    }
+
+    fn note_on() -> Command {
+        Command::Instrument(NoteOn(Pitch::default(), 1., id_discr(Pitch::default(), 1)))
+    }
+
+    fn note_off() -> Command {
+        Command::Instrument(NoteOff(id_discr(Pitch::default(), 1)))
+    }
+
+    #[test]
+    fn straight_notes_snap_to_the_nearest_step() {
+        let origin = Instant::now();
+        let quantizer = eighths(0.5);
+        assert_eq!(quantizer.snap(origin, BEAT, at(origin, 0.4)), Some(at(origin, 0.5)));
+        assert_eq!(quantizer.snap(origin, BEAT, at(origin, 0.6)), Some(at(origin, 0.5)));
+        assert_eq!(quantizer.snap(origin, BEAT, at(origin, 0.9)), Some(at(origin, 1.0)));
+    }
+
+    #[test]
+    fn swung_notes_snap_to_the_swung_step() {
+        let origin = Instant::now();
+        let quantizer = eighths(2. / 3.);
+        let swung = at(origin, 2. / 3.);
+        let snapped = quantizer.snap(origin, BEAT, at(origin, 0.6)).unwrap();
+        assert!(snapped.max(swung) - snapped.min(swung) < Duration::from_millis(1));
+    }
+
+    #[test]
+    fn late_notes_play_at_once() {
+        let origin = Instant::now();
+        let mut quantizer = eighths(0.5);
+        assert_eq!(quantizer.schedule(vec![note_on()], Some((origin, BEAT)), at(origin, 0.6)).len(), 1);
+        assert_eq!(quantizer.schedule(vec![note_off()], Some((origin, BEAT)), at(origin, 0.8)).len(), 1);
+    }
+
+    #[test]
+    fn no_grid_plays_at_once() {
+        let origin = Instant::now();
+        let mut quantizer = Quantizer::default();
+        let now = at(origin, 0.6);
+        assert_eq!(quantizer.schedule(vec![note_on()], Some((origin, BEAT)), now).len(), 1);
+        let mut quantizer = eighths(0.5);
+        assert_eq!(quantizer.schedule(vec![note_on()], None, now).len(), 1);
+    }
+
+    #[test]
+    fn note_offs_follow_their_note_on() {
+        let origin = Instant::now();
+        let mut quantizer = eighths(0.5);
+        assert!(quantizer.schedule(vec![note_on()], Some((origin, BEAT)), at(origin, 0.4)).is_empty());
+        assert!(quantizer.schedule(vec![note_off()], Some((origin, BEAT)), at(origin, 0.8)).is_empty());
+        assert!(quantizer.due(at(origin, 0.49)).is_empty());
+        assert_eq!(quantizer.due(at(origin, 0.5)).len(), 1);
+        assert!(quantizer.due(at(origin, 0.89)).is_empty());
+        assert_eq!(quantizer.due(at(origin, 0.9)).len(), 1);
+    }
+}
@@ -0,0 +1,32 @@
+use piston_window::{Button::*, ButtonArgs, ButtonState::*, Input, Input::*, Key};
+use rust_synth::core::control::tools::Command;
+use crate::control::{Control, Mode};
+
+pub fn handle_input(input: &Input, control: &mut Control) -> Vec<Command> {
+    match input {
+        Button(ButtonArgs { state: Press, button: Keyboard(key), .. }) => handle_press(*key, control),
+        Button(ButtonArgs { state: Release, button: Keyboard(key), .. }) => {
+            match key {
+                Key::Tab | Key::Escape => control.mode = Mode::Playing,
+                Key::Backquote => control.mode = Mode::Arranging,
+                _ => (),
+            }
+            vec![]
+        },
+        _ => vec![],
+    }
+}
+
+fn handle_press(key: Key, control: &mut Control) -> Vec<Command> {
+    let grid = &mut control.rhythm_grid;
+    match key {
+        Key::Space => return grid.toggle_playback(&control.clock),
+        Key::Return => grid.toggle_hit(),
+        Key::Left => grid.move_cursor(0, -1),
+        Key::Right => grid.move_cursor(0, 1),
+        Key::Up => grid.move_cursor(-1, 0),
+        Key::Down => grid.move_cursor(1, 0),
+        _ => (),
+    }
+    vec![]
+}
@@ -0,0 +1,130 @@
+use rust_synth::core::{
+    control::{synth::{Command::*, Discriminator, Id, id_discr}, tools::Command::{self, *}},
+    music_theory::{pitch::Pitch, pitch_class::PitchClass},
+};
+use crate::control::{clock::Clock, sequencer::STEPS_PER_BEAT};
+
+pub const N_STEPS: usize = 16;
+/// Keeps the grid's hits apart from the notes played on the keyboard
+const RHYTHM_DISCR: Discriminator = 6;
+const ACCENT: f64 = 1.;
+const VELOCITY: f64 = 0.7;
+
+/// A row of hits, each played at a pitch of its own through the patch playing, as the synth has no percussion
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Row {
+    Low, Mid, High, Top,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Hit {
+    Off, On, Accent,
+}
+
+/// A pattern of hits for each row, played on the clock's sixteenth notes
+pub struct RhythmGrid {
+    /// Indexed by row, then step
+    pub hits: Vec<Vec<Hit>>,
+    /// Row and step being edited
+    pub cursor: (usize, usize),
+    /// Beat of the clock the first step plays on, if it's playing
+    started: Option<f64>,
+    /// Steps played since it started
+    played: Option<u64>,
+    /// Hits sounding, released on the next step
+    holding: Vec<Id>,
+}
+
+impl Row {
+    pub const ALL: [Row; 4] = [Row::Low, Row::Mid, Row::High, Row::Top];
+
+    pub fn pitch(self) -> Pitch {
+        match self {
+            Row::Low => Pitch::new(PitchClass::C, 2),
+            Row::Mid => Pitch::new(PitchClass::D, 3),
+            Row::High => Pitch::new(PitchClass::Ds, 4),
+            Row::Top => Pitch::new(PitchClass::Fs, 5),
+        }
+    }
+}
+
+impl Default for RhythmGrid {
+    fn default() -> Self {
+        RhythmGrid {
+            hits: vec![vec![Hit::Off; N_STEPS]; Row::ALL.len()],
+            cursor: (0, 0),
+            started: None,
+            played: None,
+            holding: vec![],
+        }
+    }
+}
+
+impl RhythmGrid {
+
+    /// Cycles the hit at the cursor through off, on and accented
+    pub fn toggle_hit(&mut self) {
+        let (row, step) = self.cursor;
+        let hit = &mut self.hits[row][step];
+        *hit = match hit {
+            Hit::Off => Hit::On,
+            Hit::On => Hit::Accent,
+            Hit::Accent => Hit::Off,
+        };
+    }
+
+    pub fn move_cursor(&mut self, rows: isize, steps: isize) {
+        let (row, step) = self.cursor;
+        self.cursor = ((row as isize + rows).rem_euclid(Row::ALL.len() as isize) as usize,
+                       (step as isize + steps).rem_euclid(N_STEPS as isize) as usize);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Starts from the first step on the clock's next step, or stops
+    pub fn toggle_playback(&mut self, clock: &Clock) -> Vec<Command> {
+        if self.started.take().is_none() {
+            self.started = Some(clock.next_step(STEPS_PER_BEAT));
+            self.played = None;
+        }
+        self.release()
+    }
+
+    pub fn playhead(&self) -> Option<usize> {
+        let played = self.played? as usize;
+        self.started.map(|_| played % N_STEPS)
+    }
+
+    /// Hits of each step as the clock reaches it
+    pub fn tick(&mut self, clock: &Clock) -> Vec<Command> {
+        let beats = clock.beats();
+        let started = match self.started {
+            Some(started) if beats >= started => started,
+            _ => return vec![],
+        };
+        let count = ((beats - started) * STEPS_PER_BEAT as f64) as u64;
+        if self.played == Some(count) {
+            return vec![]
+        }
+        self.played = Some(count);
+        let step = count as usize % N_STEPS;
+        let mut commands = self.release();
+        for (row, hits) in Row::ALL.iter().zip(self.hits.iter()) {
+            let velocity = match hits[step] {
+                Hit::Off => continue,
+                Hit::On => VELOCITY,
+                Hit::Accent => ACCENT,
+            };
+            let id = id_discr(row.pitch(), RHYTHM_DISCR);
+            commands.push(Instrument(NoteOn(row.pitch(), velocity, id)));
+            self.holding.push(id);
+        }
+        commands
+    }
+
+    fn release(&mut self) -> Vec<Command> {
+        self.holding.drain(..).map(|id| Instrument(NoteOff(id))).collect()
+    }
+}
@@ -0,0 +1,142 @@
+use rust_synth::core::music_theory::{pitch::Pitch, pitch_class::PitchClass::{self, *}};
+
+const CLASSES: [PitchClass; 12] = [C, Cs, D, Ds, E, F, Fs, G, Gs, A, As, B];
+/// The note keys are laid out on the white keys, the degrees of C major
+const NATURALS: [PitchClass; 7] = [C, D, E, F, G, A, B];
+/// Octave of the C the scales are laid out from, the D key in the middle row
+const ANCHOR_OCTAVE: i32 = 4;
+
+pub const NAMES: [&str; 13] = ["major", "dorian", "phrygian", "lydian", "mixolydian", "minor", "locrian",
+                               "harmonic minor", "melodic minor", "major pentatonic", "minor pentatonic", "blues",
+                               "custom"];
+/// Semitones above the tonic of each scale but the custom one, which comes last in `NAMES`
+const PRESETS: [&[u8]; 12] = [
+    &[0, 2, 4, 5, 7, 9, 11],
+    &[0, 2, 3, 5, 7, 9, 10],
+    &[0, 1, 3, 5, 7, 8, 10],
+    &[0, 2, 4, 6, 7, 9, 11],
+    &[0, 2, 4, 5, 7, 9, 10],
+    &[0, 2, 3, 5, 7, 8, 10],
+    &[0, 1, 3, 5, 6, 8, 10],
+    &[0, 2, 3, 5, 7, 8, 11],
+    &[0, 2, 3, 5, 7, 9, 11],
+    &[0, 2, 4, 7, 9],
+    &[0, 3, 5, 7, 10],
+    &[0, 3, 5, 6, 7, 10],
+];
+pub const CUSTOM: usize = PRESETS.len();
+/// Intervals that can be added to the custom scale, from a semitone above the tonic
+pub const INTERVALS: [&str; 11] = ["m2", "M2", "m3", "M3", "4", "b5", "5", "m6", "M6", "m7", "M7"];
+
+/// Scale the note keys play in, each key stepping to the next note of the scale
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scale {
+    /// One of the presets, or `CUSTOM`
+    pub selected: usize,
+    /// Semitones above the tonic in the custom scale, the tonic always being in
+    pub custom: [bool; 12],
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        let mut custom = [false; 12];
+        PRESETS[0].iter().for_each(|semitone| custom[*semitone as usize] = true);
+        Scale { selected: 0, custom }
+    }
+}
+
+impl Scale {
+
+    pub fn name(&self) -> &'static str {
+        NAMES[self.selected]
+    }
+
+    fn semitones(&self) -> Vec<u8> {
+        match PRESETS.get(self.selected) {
+            Some(semitones) => semitones.to_vec(),
+            None => (0..12).filter(|s| *s == 0 || self.custom[*s as usize]).collect(),
+        }
+    }
+
+    /// Pitch a note key plays, counting its degree of C major as a degree of the scale from middle C.
+    /// Scales with fewer notes than the major one spread over more octaves along the rows.
+    pub fn apply(&self, pitch: Pitch) -> Pitch {
+        let degree = match degree(pitch) {
+            Some(degree) => degree,
+            None => return pitch,
+        };
+        let semitones = self.semitones();
+        let n = semitones.len() as i32;
+        let octave = ANCHOR_OCTAVE + degree.div_euclid(n);
+        let semitone = semitones[degree.rem_euclid(n) as usize] as usize;
+        Pitch::new(CLASSES[semitone], octave as i8)
+    }
+}
+
+/// Steps of a note key along the rows, counting from the D key in the middle row
+pub fn degree(pitch: Pitch) -> Option<i32> {
+    NATURALS.iter().position(|class| *class == pitch.class)
+        .map(|degree| (pitch.octave as i32 - ANCHOR_OCTAVE) * 7 + degree as i32)
+}
+
+/// MIDI note number of a pitch
+pub fn to_midi(pitch: Pitch) -> i32 {
+    let class = CLASSES.iter().position(|class| *class == pitch.class).unwrap_or(0) as i32;
+    (pitch.octave as i32 + 1) * 12 + class
+}
+
+/// Pitch of a MIDI note number, in 12 tone equal temperament
+pub fn from_midi(note: i32) -> Pitch {
+    Pitch::new(CLASSES[note.rem_euclid(12) as usize], (note.div_euclid(12) - 1) as i8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midi_round_trip() {
+        assert_eq!(to_midi(Pitch::new(A, 4)), 69);
+        assert_eq!(to_midi(Pitch::new(C, -1)), 0);
+        for note in 0..128 {
+            assert_eq!(to_midi(from_midi(note)), note);
+        }
+    }
+
+    #[test]
+    fn major_plays_the_keys_as_they_are() {
+        let scale = Scale::default();
+        for class in NATURALS.iter() {
+            for octave in 3..6 {
+                let pitch = Pitch::new(*class, octave);
+                assert_eq!(scale.apply(pitch), pitch);
+            }
+        }
+    }
+
+    #[test]
+    fn minor_flattens_the_third() {
+        let minor = Scale { selected: 5, ..Default::default() };
+        assert_eq!(minor.apply(Pitch::new(E, 4)), Pitch::new(Ds, 4));
+        assert_eq!(minor.apply(Pitch::new(C, 5)), Pitch::new(C, 5));
+    }
+
+    #[test]
+    fn pentatonic_spreads_over_more_octaves() {
+        let pentatonic = Scale { selected: 9, ..Default::default() };
+        assert_eq!(pentatonic.apply(Pitch::new(A, 4)), Pitch::new(C, 5));
+        assert_eq!(pentatonic.apply(Pitch::new(B, 3)), Pitch::new(A, 3));
+    }
+
+    #[test]
+    fn custom_always_has_the_tonic() {
+        let custom = Scale { selected: CUSTOM, custom: [false; 12] };
+        assert_eq!(custom.apply(Pitch::new(D, 4)), Pitch::new(C, 5));
+    }
+
+    #[test]
+    fn sharps_are_left_alone() {
+        let minor = Scale { selected: 5, ..Default::default() };
+        assert_eq!(minor.apply(Pitch::new(Fs, 4)), Pitch::new(Fs, 4));
+    }
+}
@@ -0,0 +1,162 @@
+use rust_synth::core::{
+    control::{synth::{Command::*, Discriminator, Id, id_discr}, tools::Command::{self, *}},
+    music_theory::pitch::Pitch,
+};
+use crate::control::clock::Clock;
+
+pub const N_PATTERNS: usize = 4;
+pub const PATTERN_LENGTHS: [usize; 2] = [16, 32];
+/// Steps are sixteenth notes
+pub const STEPS_PER_BEAT: u32 = 4;
+/// Keeps the sequencer's notes apart from the ones played on the keyboard
+const SEQUENCER_DISCR: Discriminator = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Step {
+    Rest,
+    Note {
+        pitch: Pitch,
+        velocity: f64,
+        /// Fraction of the step the note is held for
+        gate: f64,
+    },
+    /// Carries on the note from the step before
+    Tie,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub steps: Vec<Step>,
+}
+
+/// Patterns of steps, played one at a time on the tempo's sixteenth notes
+pub struct Sequencer {
+    pub patterns: Vec<Pattern>,
+    /// Pattern playing and being edited
+    pub pattern: usize,
+    /// Step being edited
+    pub cursor: usize,
+    /// Beat of the clock the first step plays on, if it's playing
+    started: Option<f64>,
+    /// Steps played since it started
+    played: Option<u64>,
+    /// Note being held, the beat it's released on and the gate of its step
+    holding: Option<(Id, f64, f64)>,
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Pattern { steps: vec![Step::Rest; PATTERN_LENGTHS[0]] }
+    }
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Sequencer {
+            patterns: vec![Pattern::default(); N_PATTERNS],
+            pattern: 0,
+            cursor: 0,
+            started: None,
+            played: None,
+            holding: None,
+        }
+    }
+}
+
+impl Sequencer {
+
+    pub fn steps(&self) -> &[Step] {
+        &self.patterns[self.pattern].steps
+    }
+
+    pub fn step_mut(&mut self) -> &mut Step {
+        &mut self.patterns[self.pattern].steps[self.cursor]
+    }
+
+    /// Switches the pattern between the lengths, keeping the first steps when shortened
+    pub fn toggle_length(&mut self) {
+        let steps = &mut self.patterns[self.pattern].steps;
+        let length = if steps.len() == PATTERN_LENGTHS[0] { PATTERN_LENGTHS[1] } else { PATTERN_LENGTHS[0] };
+        steps.resize(length, Step::Rest);
+        self.cursor = self.cursor.min(length - 1);
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) {
+        let length = self.steps().len() as isize;
+        self.cursor = (self.cursor as isize + offset).rem_euclid(length) as usize;
+    }
+
+    pub fn select_pattern(&mut self, pattern: usize) {
+        self.pattern = pattern;
+        self.cursor = self.cursor.min(self.steps().len() - 1);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Starts from the first step on the clock's next step, or stops releasing the note held
+    pub fn toggle_playback(&mut self, clock: &Clock) -> Vec<Command> {
+        if self.started.take().is_none() {
+            self.started = Some(clock.next_step(STEPS_PER_BEAT));
+            self.played = None;
+        }
+        self.release()
+    }
+
+    /// Step playing, if it is
+    pub fn playhead(&self) -> Option<usize> {
+        let played = self.played? as usize;
+        self.started.map(|_| played % self.steps().len())
+    }
+
+    /// Notes of the steps as the clock reaches them
+    pub fn tick(&mut self, clock: &Clock) -> Vec<Command> {
+        let mut commands = vec![];
+        let beats = clock.beats();
+        if let Some(started) = self.started.filter(|started| beats >= *started) {
+            let count = ((beats - started) * STEPS_PER_BEAT as f64) as u64;
+            if self.played != Some(count) {
+                self.played = Some(count);
+                let step_start = started + count as f64 / STEPS_PER_BEAT as f64;
+                commands.extend(self.play_step(count as usize, step_start));
+            }
+        }
+        if let Some((id, until, _)) = self.holding {
+            if beats >= until {
+                commands.push(Instrument(NoteOff(id)));
+                self.holding = None;
+            }
+        }
+        commands
+    }
+
+    /// A note followed by a tie is held until the tie, which then holds it for its gate
+    fn play_step(&mut self, count: usize, step_start: f64) -> Vec<Command> {
+        let steps = self.steps();
+        let tied = steps[(count + 1) % steps.len()] == Step::Tie;
+        let step = steps[count % steps.len()];
+        let step_length = 1. / STEPS_PER_BEAT as f64;
+        let until = |gate: f64| step_start + if tied { step_length * 2. } else { step_length * gate };
+        match step {
+            Step::Rest => vec![],
+            Step::Note { pitch, velocity, gate } => {
+                let mut commands = self.release();
+                let id = id_discr(pitch, SEQUENCER_DISCR);
+                commands.push(Instrument(NoteOn(pitch, velocity, id)));
+                self.holding = Some((id, until(gate), gate));
+                commands
+            },
+            Step::Tie => {
+                if let Some((id, _, gate)) = self.holding {
+                    self.holding = Some((id, until(gate), gate));
+                }
+                vec![]
+            },
+        }
+    }
+
+    fn release(&mut self) -> Vec<Command> {
+        self.holding.take().map_or(vec![], |(id, _, _)| vec![Instrument(NoteOff(id))])
+    }
+}
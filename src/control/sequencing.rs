@@ -0,0 +1,78 @@
+use piston_window::{Button::*, ButtonArgs, ButtonState::*, Input, Input::*, Key};
+use rust_synth::core::control::{synth::{Command::*, id_discr}, tools::Command::{self, *}};
+use crate::control::{Control, Mode, sequencer::{Step, N_PATTERNS}};
+
+/// Default velocity and gate of the steps entered
+const VELOCITY: f64 = 0.8;
+const GATE: f64 = 0.5;
+/// Change of velocity or gate by each press of their keys
+const STEP: f64 = 0.1;
+
+pub fn handle_input(input: &Input, control: &mut Control) -> Vec<Command> {
+    match input {
+        Button(ButtonArgs { state, button: Keyboard(key), .. }) => match state {
+            Press => handle_press(*key, control),
+            Release => handle_release(*key, control),
+        },
+        _ => vec![],
+    }
+}
+
+/// Note keys enter a note at the cursor and move on, sounding it while held
+fn handle_press(key: Key, control: &mut Control) -> Vec<Command> {
+    if let Some((pitch, discr)) = control.pitches(key) {
+        let sequencer = &mut control.sequencer;
+        *sequencer.step_mut() = Step::Note { pitch, velocity: VELOCITY, gate: GATE };
+        sequencer.move_cursor(1);
+        return vec![Instrument(NoteOn(pitch, VELOCITY, id_discr(pitch, discr)))]
+    }
+    let sequencer = &mut control.sequencer;
+    match key {
+        Key::Space => return sequencer.toggle_playback(&control.clock),
+        Key::Left => sequencer.move_cursor(-1),
+        Key::Right => sequencer.move_cursor(1),
+        Key::Up => sequencer.move_cursor(-16),
+        Key::Down => sequencer.move_cursor(16),
+        Key::Backspace => *sequencer.step_mut() = Step::Rest,
+        Key::Return => {
+            *sequencer.step_mut() = Step::Tie;
+            sequencer.move_cursor(1);
+        },
+        Key::Minus => adjust(sequencer.step_mut(), -STEP, 0.),
+        Key::Equals => adjust(sequencer.step_mut(), STEP, 0.),
+        Key::LeftBracket => adjust(sequencer.step_mut(), 0., -STEP),
+        Key::RightBracket => adjust(sequencer.step_mut(), 0., STEP),
+        Key::Quote => sequencer.toggle_length(),
+        Key::D1 | Key::D2 | Key::D3 | Key::D4 => sequencer.select_pattern(pattern_index(key)),
+        _ => (),
+    }
+    vec![]
+}
+
+fn handle_release(key: Key, control: &mut Control) -> Vec<Command> {
+    match key {
+        Key::Tab | Key::Escape => control.mode = Mode::Playing,
+        Key::Backquote => control.mode = Mode::Rhythm,
+        _ => if let Some((pitch, discr)) = control.pitches(key) {
+            return vec![Instrument(NoteOff(id_discr(pitch, discr)))]
+        },
+    }
+    vec![]
+}
+
+fn adjust(step: &mut Step, velocity_change: f64, gate_change: f64) {
+    if let Step::Note { velocity, gate, .. } = step {
+        *velocity = (*velocity + velocity_change).clamp(STEP, 1.);
+        *gate = (*gate + gate_change).clamp(STEP, 1.);
+    }
+}
+
+fn pattern_index(key: Key) -> usize {
+    let index = match key {
+        Key::D2 => 1,
+        Key::D3 => 2,
+        Key::D4 => 3,
+        _ => 0,
+    };
+    index.min(N_PATTERNS - 1)
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+pub const N_LOOPS: usize = 5;
+/// Longest length a recording can be set to stop at
+pub const MAX_BARS: u32 = 16;
+
+/// What the GUI keeps about one of the synth's loops
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Slot {
+    /// Bars after which recording stops by itself, free length if None
+    pub bars: Option<u32>,
+    /// Duration of the last take
+    pub length: Option<Duration>,
+    recording_since: Option<Instant>,
+    playing_since: Option<Instant>,
+    /// Recording was told to stop, which the synth's view doesn't show yet
+    stopping: bool,
+}
+
+impl Slot {
+
+    pub fn is_recording(&self) -> bool {
+        self.recording_since.is_some()
+    }
+
+    pub fn recording_since(&self) -> Option<Instant> {
+        self.recording_since
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing_since.is_some()
+    }
+
+    /// A new take replaces what the loop had
+    pub fn start_recording(&mut self, now: Instant) {
+        self.recording_since = Some(now);
+        self.length = None;
+    }
+
+    pub fn stop_recording(&mut self, now: Instant) {
+        if let Some(since) = self.recording_since {
+            self.length = Some(now - since);
+        }
+        self.recording_since = None;
+        self.stopping = false;
+    }
+
+    pub fn follow_playback(&mut self, playing: bool, now: Instant) {
+        match (self.playing_since, playing) {
+            (None, true) => self.playing_since = Some(now),
+            (Some(_), false) => self.playing_since = None,
+            _ => (),
+        }
+    }
+
+    /// Whether the recording reached the bars it's set to, and has to be stopped.
+    /// It's then taken as stopping, until the synth's view catches up.
+    pub fn stop_if_due(&mut self, bar: Option<Duration>, now: Instant) -> bool {
+        let length = self.bars.zip(bar).map(|(n, bar)| bar * n);
+        match (self.recording_since, length) {
+            (Some(since), Some(length)) if !self.stopping && now - since >= length => {
+                self.stopping = true;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// How far the playback is into the loop, between 0 and 1
+    pub fn playhead(&self, now: Instant) -> Option<f64> {
+        let length = self.length?.as_secs_f64();
+        let elapsed = (now - self.playing_since?).as_secs_f64();
+        Some(elapsed % length / length)
+    }
+
+    pub fn length_in_bars(&self, bar: Duration) -> Option<f64> {
+        Some(self.length?.as_secs_f64() / bar.as_secs_f64())
+    }
+}
@@ -0,0 +1,306 @@
+use std::fs;
+use std::io;
+use rust_synth::core::music_theory::diatonic_scale::Key;
+use crate::control::{slots::N_LOOPS, patches::N_PATCHES, tempo::{MIN_BPM, MAX_BPM}, clock::Clock};
+
+pub const KEYS: [Key; 12] = [Key::C, Key::Db, Key::D, Key::Eb, Key::E, Key::F, Key::Gb, Key::G, Key::Ab, Key::A,
+                             Key::Bb, Key::B];
+const KEY_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+pub const FILE: &str = "song.txt";
+const MAX_BARS: u32 = 64;
+
+/// Setting of a section changed by the arrangement keys
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Field {
+    Bars, Patch, Tempo, Key
+}
+
+/// A part of the song, setting up what plays for a number of bars
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub bars: u32,
+    /// Whether each loop plays
+    pub loops: Vec<bool>,
+    /// Patch that plays, as the synth plays one at a time
+    pub patch: usize,
+    /// Tempo and key to change to, if any
+    pub bpm: Option<f64>,
+    pub key: Option<Key>,
+}
+
+/// Sections played one after the other, counting the clock's bars from when the song starts
+pub struct Song {
+    pub sections: Vec<Section>,
+    /// Section and setting being edited
+    pub selected: usize,
+    pub field: Field,
+    /// Outcome of the last save or load
+    pub status: Option<String>,
+    /// Beat of the clock the song started on, while it plays
+    started: Option<f64>,
+    /// Section playing and how far into it, in bars
+    position: Option<(usize, f64)>,
+    /// Section whose setup was last applied
+    entered: Option<usize>,
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Section { bars: 4, loops: vec![false; N_LOOPS], patch: 0, bpm: None, key: None }
+    }
+}
+
+impl Default for Song {
+    fn default() -> Self {
+        Song { sections: vec![], selected: 0, field: Field::Bars, status: None, started: None, position: None,
+               entered: None }
+    }
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [Field::Bars, Field::Patch, Field::Tempo, Field::Key];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Bars => "bars",
+            Field::Patch => "patch",
+            Field::Tempo => "tempo",
+            Field::Key => "key",
+        }
+    }
+}
+
+impl Section {
+
+    /// Steps a setting up or down, starting the tempo and key from the ones playing when unset
+    pub fn change(&mut self, field: Field, step: i32, bpm: Option<f64>, key: Key) {
+        match field {
+            Field::Bars => self.bars = (self.bars as i32 + step).clamp(1, MAX_BARS as i32) as u32,
+            Field::Patch => self.patch = (self.patch as i32 + step).rem_euclid(N_PATCHES as i32) as usize,
+            Field::Tempo => self.bpm = match self.bpm {
+                Some(bpm) => Some((bpm.round() + step as f64).clamp(MIN_BPM, MAX_BPM)),
+                None => bpm.map(f64::round),
+            },
+            Field::Key => self.key = Some(match self.key {
+                Some(key) => KEYS[(key_index(key) as i32 + step).rem_euclid(KEYS.len() as i32) as usize],
+                None => key,
+            }),
+        }
+    }
+
+    /// Leaves the tempo or key as they were when the section starts
+    pub fn unset(&mut self, field: Field) {
+        match field {
+            Field::Tempo => self.bpm = None,
+            Field::Key => self.key = None,
+            _ => (),
+        }
+    }
+
+    pub fn display(&self, field: Field) -> String {
+        match field {
+            Field::Bars => self.bars.to_string(),
+            Field::Patch => (self.patch + 1).to_string(),
+            Field::Tempo => self.bpm.map_or("-".to_string(), |bpm| format!("{:.1}", bpm)),
+            Field::Key => self.key.map_or("-".to_string(), |key| key_name(key).to_string()),
+        }
+    }
+
+    /// As a line of the song's file, e.g. `bars=8 loops=1,3 patch=1 bpm=120.0 key=Eb`
+    fn to_line(&self) -> String {
+        let loops = self.loops.iter().enumerate()
+            .filter(|(_, on)| **on)
+            .map(|(n, _)| (n + 1).to_string())
+            .collect::<Vec<_>>();
+        let mut line = format!("bars={} loops={} patch={}", self.bars, loops.join(","), self.patch + 1);
+        if let Some(bpm) = self.bpm {
+            line.push_str(format!(" bpm={:.1}", bpm).as_str());
+        }
+        if let Some(key) = self.key {
+            line.push_str(format!(" key={}", key_name(key)).as_str());
+        }
+        line
+    }
+
+    fn from_line(line: &str) -> Result<Section, String> {
+        let mut section = Section::default();
+        for field in line.split_whitespace() {
+            let (name, value) = field.split_at(field.find('=').ok_or(format!("no value in {}", field))?);
+            let value = &value[1..];
+            let invalid = || format!("invalid {}: {}", name, value);
+            match name {
+                "bars" => section.bars = value.parse().map_err(|_| invalid())?,
+                "loops" => for n in value.split(',').filter(|n| !n.is_empty()) {
+                    let n: usize = n.parse().map_err(|_| invalid())?;
+                    *section.loops.get_mut(n.wrapping_sub(1)).ok_or_else(invalid)? = true;
+                },
+                "patch" => section.patch = match value.parse::<usize>() {
+                    Ok(n) if (1..=N_PATCHES).contains(&n) => n - 1,
+                    _ => return Err(invalid()),
+                },
+                "bpm" => section.bpm = match value.parse::<f64>() {
+                    Ok(bpm) if (MIN_BPM..=MAX_BPM).contains(&bpm) => Some(bpm),
+                    _ => return Err(invalid()),
+                },
+                "key" => section.key = Some(KEYS[KEY_NAMES.iter().position(|k| *k == value).ok_or_else(invalid)?]),
+                _ => return Err(format!("unknown field: {}", name)),
+            }
+        }
+        Ok(section)
+    }
+}
+
+impl Song {
+
+    pub fn section_mut(&mut self) -> Option<&mut Section> {
+        self.sections.get_mut(self.selected)
+    }
+
+    /// Adds a copy of the selected section after it, or a first section
+    pub fn add_section(&mut self) {
+        match self.sections.get(self.selected).cloned() {
+            Some(section) => {
+                self.sections.insert(self.selected + 1, section);
+                self.selected += 1;
+            },
+            None => self.sections.push(Section::default()),
+        }
+    }
+
+    pub fn remove_section(&mut self) {
+        if self.selected < self.sections.len() {
+            self.sections.remove(self.selected);
+            self.selected = self.selected.min(self.sections.len().saturating_sub(1));
+        }
+    }
+
+    pub fn select(&mut self, offset: i32) {
+        let last = self.sections.len().saturating_sub(1) as i32;
+        self.selected = (self.selected as i32 + offset).clamp(0, last) as usize;
+    }
+
+    pub fn select_field(&mut self, offset: i32) {
+        let index = Field::ALL.iter().position(|f| *f == self.field).unwrap_or(0) as i32;
+        self.field = Field::ALL[(index + offset).rem_euclid(Field::ALL.len() as i32) as usize];
+    }
+
+    /// Starts on the clock's next beat, or stops
+    pub fn toggle_playback(&mut self, clock: &Clock) {
+        let starting = self.started.is_none() && !self.sections.is_empty();
+        self.started = if starting { Some(clock.next_step(1)) } else { None };
+        self.position = None;
+        self.entered = None;
+    }
+
+    /// Follows the clock's bars through the sections, telling when the last one is over, which stops the song
+    pub fn tick(&mut self, clock: &Clock, beats_per_bar: u32) -> bool {
+        let started = match self.started {
+            Some(started) => started,
+            None => return false,
+        };
+        let mut bars = (clock.beats() - started).max(0.) / beats_per_bar as f64;
+        for (i, section) in self.sections.iter().enumerate() {
+            if bars < section.bars as f64 {
+                self.position = Some((i, bars));
+                return false
+            }
+            bars -= section.bars as f64;
+        }
+        self.started = None;
+        self.position = None;
+        self.status = Some("song finished".to_string());
+        true
+    }
+
+    /// Section playing and how far into it, in bars
+    pub fn position(&self) -> Option<(usize, f64)> {
+        self.position
+    }
+
+    /// Section just reached, whose setup is to be applied
+    pub fn enter(&mut self, section: Option<usize>) -> Option<&Section> {
+        if section == self.entered {
+            return None
+        }
+        self.entered = section;
+        self.sections.get(section?)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let lines = self.sections.iter().map(|section| section.to_line()).collect::<Vec<_>>();
+        fs::write(FILE, lines.join("\n") + "\n")
+    }
+
+    pub fn load(&mut self) -> io::Result<()> {
+        let sections = fs::read_to_string(FILE)?.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Section::from_line)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.sections = sections;
+        self.selected = 0;
+        self.started = None;
+        self.position = None;
+        self.entered = None;
+        Ok(())
+    }
+}
+
+pub fn key_name(key: Key) -> &'static str {
+    KEY_NAMES[key_index(key)]
+}
+
+pub fn key_index(key: Key) -> usize {
+    KEYS.iter().position(|k| *k == key).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn line_round_trip() {
+        let mut loops = vec![false; N_LOOPS];
+        loops[0] = true;
+        loops[2] = true;
+        let sections = [
+            Section::default(),
+            Section { bars: 8, loops, patch: 1, bpm: Some(120.), key: Some(Key::Eb) },
+        ];
+        for section in sections.iter() {
+            assert_eq!(Section::from_line(&section.to_line()).as_ref(), Ok(section));
+        }
+        assert_eq!(sections[1].to_line(), "bars=8 loops=1,3 patch=2 bpm=120.0 key=Eb");
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let lines = ["bars", "bars=x", "loops=0", "patch=9", "bpm=fast", "bpm=NaN", "bpm=5", "key=H", "tempo=120"];
+        for line in lines.iter() {
+            assert!(Section::from_line(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn plays_through_the_sections_and_finishes() {
+        let sections = vec![Section { bars: 1, ..Default::default() }, Section { bars: 2, ..Default::default() }];
+        let mut song = Song { sections, ..Default::default() };
+        let mut clock = Clock::default();
+        let start = Instant::now();
+        let beat = Duration::from_millis(500);
+        clock.tick(beat, start);
+        song.toggle_playback(&clock);
+        let beats = |beats: u32| start + beat * beats;
+        clock.tick(beat, beats(2));
+        assert!(!song.tick(&clock, 4));
+        assert_eq!(song.position(), Some((0, 0.5)));
+        clock.tick(beat, beats(6));
+        assert!(!song.tick(&clock, 4));
+        assert_eq!(song.position(), Some((1, 0.5)));
+        clock.tick(beat, beats(12));
+        assert!(song.tick(&clock, 4));
+        assert_eq!(song.position(), None);
+        assert!(!song.tick(&clock, 4));
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use rust_synth::core::music_theory::Hz;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Feel {
+    Straight, Dotted, Triplet
+}
+
+/// A note length relative to the tempo, e.g. 1/8 dotted
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Division {
+    pub denominator: u8,
+    pub feel: Feel,
+}
+
+const fn division(denominator: u8, feel: Feel) -> Division {
+    Division { denominator, feel }
+}
+
+/// From the longest to the shortest
+pub const DIVISIONS: [Division; 18] = [
+    division(1, Feel::Dotted), division(1, Feel::Straight), division(2, Feel::Dotted),
+    division(1, Feel::Triplet), division(2, Feel::Straight), division(4, Feel::Dotted),
+    division(2, Feel::Triplet), division(4, Feel::Straight), division(8, Feel::Dotted),
+    division(4, Feel::Triplet), division(8, Feel::Straight), division(16, Feel::Dotted),
+    division(8, Feel::Triplet), division(16, Feel::Straight), division(32, Feel::Dotted),
+    division(16, Feel::Triplet), division(32, Feel::Straight), division(32, Feel::Triplet),
+];
+
+pub const QUARTER: Division = division(4, Feel::Straight);
+
+pub const MIN_BPM: f64 = 20.;
+pub const MAX_BPM: f64 = 300.;
+/// Taps sent to set a tempo, a few intervals being enough for the synth to follow
+const TAPS: u32 = 4;
+/// How far off in BPM the synth's tempo can settle before it's tapped again
+const TOLERANCE: f64 = 0.5;
+/// Times a tempo is tapped again when the synth settles off it
+const RETRIES: u32 = 2;
+
+pub fn bpm(beat: Duration) -> f64 {
+    60. / beat.as_secs_f64()
+}
+
+pub fn beat(bpm: f64) -> Duration {
+    Duration::from_secs_f64(60. / bpm)
+}
+
+/// Sets the synth's tempo the only way it takes one, tapping it at the period wanted, in whole BPM.
+/// Taps are never closer than that period, even when the tempo changes while tapping,
+/// and are timed from when the last one was due so that being sent late doesn't add up.
+/// The tempo is tapped again when the synth settles too far off it.
+#[derive(Default)]
+pub struct Tapper {
+    /// Tempo being tapped, kept afterwards until the synth is seen following it
+    target: Option<f64>,
+    taps_left: u32,
+    last_tap: Option<Instant>,
+    retries: u32,
+}
+
+impl Tapper {
+
+    /// Tempo to tap, ignored when it isn't a number
+    pub fn set_bpm(&mut self, bpm: f64) {
+        if !bpm.is_finite() { return }
+        self.target = Some(bpm.round().clamp(MIN_BPM, MAX_BPM));
+        self.taps_left = TAPS;
+        self.retries = RETRIES;
+    }
+
+    /// Tempo in BPM being tapped, until it's done
+    pub fn target(&self) -> Option<f64> {
+        self.target.filter(|_| self.taps_left > 0)
+    }
+
+    /// A tap made by hand, which takes over
+    pub fn tap(&mut self, now: Instant) {
+        self.target = None;
+        self.last_tap = Some(now);
+    }
+
+    /// Whether the next tap is due
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let first = self.taps_left == TAPS;
+        let due = match (self.target(), self.last_tap) {
+            (Some(bpm), Some(last)) if now - last >= beat(bpm) => {
+                let stalled = now - last >= beat(bpm) * 3 / 2;
+                Some(if first || stalled { now } else { last + beat(bpm) })
+            },
+            (Some(_), Some(_)) => None,
+            (Some(_), None) => Some(now),
+            _ => None,
+        };
+        if let Some(tap) = due {
+            self.last_tap = Some(tap);
+            self.taps_left -= 1;
+        }
+        due.is_some()
+    }
+
+    /// Checks the synth's pulse once it had a couple of beats to follow the taps, tapping again if it's off
+    pub fn follow(&mut self, period: Duration, now: Instant) {
+        let (target, last) = match (self.target, self.last_tap) {
+            (Some(target), Some(last)) if self.taps_left == 0 => (target, last),
+            _ => return,
+        };
+        if now - last < beat(target) * 2 {
+            return
+        }
+        if (bpm(period) - target).abs() >= TOLERANCE && self.retries > 0 {
+            self.retries -= 1;
+            self.taps_left = TAPS;
+        } else {
+            self.target = None;
+        }
+    }
+}
+
+impl Division {
+
+    /// Length counted in beats, taking a beat as a quarter note
+    pub fn beats(self) -> f64 {
+        let straight = 4. / self.denominator as f64;
+        match self.feel {
+            Feel::Straight => straight,
+            Feel::Dotted => straight * 1.5,
+            Feel::Triplet => straight * 2. / 3.,
+        }
+    }
+
+    /// Cycles per second of something repeating once every division
+    pub fn freq(self, beat: Duration) -> Hz {
+        1. / (beat.as_secs_f64() * self.beats())
+    }
+
+    pub fn index(self) -> usize {
+        DIVISIONS.iter().position(|d| *d == self).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Division {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = match self.feel {
+            Feel::Straight => "",
+            Feel::Dotted => ".",
+            Feel::Triplet => "t",
+        };
+        write!(f, "1/{}{}", self.denominator, suffix)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs;
+use std::io;
+use rust_synth::core::music_theory::{Hz, pitch::Pitch};
+use crate::control::{keymap, scales, config::CONCERT_A4};
+
+pub const SCALE_FILE: &str = "tuning.scl";
+/// Optional, the notes are mapped one to one from middle C otherwise
+pub const MAPPING_FILE: &str = "tuning.kbm";
+const A4_MIDI: i32 = 69;
+
+/// A Scala tuning: the degrees of a scale in cents, and how they map to MIDI notes.
+/// The synth only plays equal tempered semitones, so each degree is played at the nearest one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    pub description: String,
+    /// Cents of each degree above the first, the last one being the period, usually an octave
+    cents: Vec<f64>,
+    mapping: Mapping,
+}
+
+/// Keyboard mapping, as in a Scala .kbm file
+#[derive(Clone, Debug, PartialEq)]
+struct Mapping {
+    /// MIDI note playing the first degree
+    middle_note: i32,
+    reference_note: i32,
+    reference_freq: Hz,
+    /// Degree each key of a repeating pattern plays, if any, or empty for one degree per key
+    keys: Vec<Option<usize>>,
+    /// Degree the pattern of keys repeats at
+    period_degree: usize,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Mapping { middle_note: 60, reference_note: A4_MIDI, reference_freq: CONCERT_A4, keys: vec![], period_degree: 0 }
+    }
+}
+
+impl Tuning {
+
+    pub fn load() -> io::Result<Tuning> {
+        let (description, cents) = parse_scale(&fs::read_to_string(SCALE_FILE)?).map_err(invalid_data)?;
+        let mapping = match fs::read_to_string(MAPPING_FILE) {
+            Ok(text) => parse_mapping(&text).map_err(invalid_data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Mapping::default(),
+            Err(e) => return Err(e),
+        };
+        let tuning = Tuning { description, cents, mapping };
+        match tuning.same_pitch_keys() {
+            Some((a, b)) => Err(invalid_data(format!("keys {} and {} round to the same semitone", a, b))),
+            None => Ok(tuning),
+        }
+    }
+
+    /// Two keys of a row that play the same pitch, if any: they'd send the same note id,
+    /// so that releasing either would stop both
+    fn same_pitch_keys(&self) -> Option<(&'static str, &'static str)> {
+        for row in keymap::ROWS.iter() {
+            let pitches = row.iter()
+                .filter_map(|(key, label)| Some((self.key_pitch(keymap::pitches(*key)?.0, 0.)?.0, *label)))
+                .collect::<Vec<_>>();
+            for (i, (pitch, label)) in pitches.iter().enumerate() {
+                if let Some((_, other)) = pitches[i + 1..].iter().find(|(other, _)| other == pitch) {
+                    return Some((label, other))
+                }
+            }
+        }
+        None
+    }
+
+    /// Pitch the synth plays for a note key, the nearest in equal temperament, and how many cents it's off.
+    /// The offset moves the whole tuning, as set by the reference pitch.
+    pub fn key_pitch(&self, key_pitch: Pitch, offset_cents: f64) -> Option<(Pitch, f64)> {
+        let note = self.mapping.middle_note + scales::degree(key_pitch)?;
+        let freq = self.freq(note)? * 2f64.powf(offset_cents / 1200.);
+        let midi = A4_MIDI as f64 + 12. * (freq / CONCERT_A4).log2();
+        let nearest = midi.round();
+        Some((scales::from_midi(nearest as i32), (midi - nearest) * 100.))
+    }
+
+    fn freq(&self, note: i32) -> Option<Hz> {
+        let cents = self.cents_from_middle(note)? - self.cents_from_middle(self.mapping.reference_note)?;
+        Some(self.mapping.reference_freq * 2f64.powf(cents / 1200.))
+    }
+
+    fn cents_from_middle(&self, note: i32) -> Option<f64> {
+        let steps = note - self.mapping.middle_note;
+        let degree = match self.mapping.keys.len() as i32 {
+            0 => steps,
+            size => {
+                let key = self.mapping.keys[steps.rem_euclid(size) as usize]? as i32;
+                key + steps.div_euclid(size) * self.mapping.period_degree as i32
+            },
+        };
+        let size = self.cents.len() as i32;
+        let period = self.cents.last().copied().unwrap_or(1200.);
+        let within = match degree.rem_euclid(size) {
+            0 => 0.,
+            d => self.cents[d as usize - 1],
+        };
+        Some(degree.div_euclid(size) as f64 * period + within)
+    }
+}
+
+/// Lines of a Scala file, skipping the comments starting with !, indented or not
+fn lines(text: &str) -> impl Iterator<Item=&str> {
+    text.lines().map(str::trim).filter(|line| !line.starts_with('!'))
+}
+
+/// Description and degrees of a .scl file, in cents
+fn parse_scale(text: &str) -> Result<(String, Vec<f64>), String> {
+    let mut lines = lines(text);
+    let description = lines.next().ok_or("missing description")?.to_string();
+    let count: usize = lines.next().and_then(|line| line.parse().ok()).ok_or("missing number of notes")?;
+    let cents = lines.filter(|line| !line.is_empty()).take(count)
+        .map(parse_degree)
+        .collect::<Result<Vec<_>, _>>()?;
+    if cents.len() < count || count == 0 {
+        return Err(format!("expected {} notes, found {}", count, cents.len()))
+    }
+    Ok((description, cents))
+}
+
+/// A degree in cents when it has a dot, or else a ratio like 3/2 or 2
+fn parse_degree(line: &str) -> Result<f64, String> {
+    let value = line.split_whitespace().next().unwrap_or_default();
+    let invalid = || format!("invalid note: {}", line);
+    if value.contains('.') {
+        return value.parse().map_err(|_| invalid())
+    }
+    let mut parts = value.splitn(2, '/');
+    let numerator: f64 = parts.next().and_then(|n| n.parse().ok()).ok_or_else(invalid)?;
+    let denominator: f64 = parts.next().map_or(Some(1.), |d| d.parse().ok()).ok_or_else(invalid)?;
+    if numerator <= 0. || denominator <= 0. {
+        return Err(invalid())
+    }
+    Ok(1200. * (numerator / denominator).log2())
+}
+
+/// A .kbm file: map size, first and last note, middle note, reference note and frequency,
+/// the degree the pattern repeats at and then the degree of each key, or x for keys left silent
+fn parse_mapping(text: &str) -> Result<Mapping, String> {
+    let fields = lines(text).filter(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().next().unwrap_or_default())
+        .collect::<Vec<_>>();
+    let number = |i: usize, name: &str| fields.get(i).and_then(|field| field.parse::<f64>().ok())
+        .ok_or(format!("missing {}", name));
+    let size = number(0, "map size")? as usize;
+    let keys = fields.iter().skip(7).take(size)
+        .map(|field| match *field {
+            "x" => Ok(None),
+            degree => degree.parse().map(Some).map_err(|_| format!("invalid key: {}", degree)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.len() < size {
+        return Err(format!("expected {} keys, found {}", size, keys.len()))
+    }
+    Ok(Mapping {
+        middle_note: number(3, "middle note")? as i32,
+        reference_note: number(4, "reference note")? as i32,
+        reference_freq: number(5, "reference frequency")?,
+        period_degree: number(6, "octave degree")? as usize,
+        keys,
+    })
+}
+
+fn invalid_data(e: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_synth::core::music_theory::pitch_class::PitchClass::*;
+
+    const FIFTHS: &str = "! fifths.scl
+!
+A fifth and an octave
+ 2
+!
+ 3/2
+ 2/1 ! the period
+";
+
+    const ON_MIDDLE_C: &str = "! size, first and last note, middle note, reference note and frequency, octave degree
+0
+0
+127
+60
+60
+261.6255653
+2
+";
+
+    fn tuning(scale: &str, mapping: &str) -> Tuning {
+        let (description, cents) = parse_scale(scale).unwrap();
+        Tuning { description, cents, mapping: parse_mapping(mapping).unwrap() }
+    }
+
+    #[test]
+    fn parses_degrees() {
+        assert_eq!(parse_degree("100.0"), Ok(100.));
+        assert_eq!(parse_degree("-5.5 cents"), Ok(-5.5));
+        assert_eq!(parse_degree("2"), Ok(1200.));
+        assert!((parse_degree("3/2").unwrap() - 701.955).abs() < 0.001);
+        assert!(parse_degree("0/1").is_err());
+        assert!(parse_degree("fifth").is_err());
+    }
+
+    #[test]
+    fn parses_scales_skipping_comments() {
+        let (description, cents) = parse_scale(FIFTHS).unwrap();
+        assert_eq!(description, "A fifth and an octave");
+        assert_eq!(cents.len(), 2);
+        assert_eq!(cents[1], 1200.);
+        assert!(parse_scale("short\n3\n100.0\n").is_err());
+        assert!(parse_scale("none\n0\n").is_err());
+    }
+
+    #[test]
+    fn indented_comments_are_skipped() {
+        let (_, cents) = parse_scale("scale\n 1\n   ! not a degree\n 2/1\n").unwrap();
+        assert_eq!(cents, vec![1200.]);
+    }
+
+    #[test]
+    fn parses_mappings() {
+        let mapping = parse_mapping(ON_MIDDLE_C).unwrap();
+        assert_eq!(mapping.middle_note, 60);
+        assert_eq!(mapping.reference_note, 60);
+        assert_eq!(mapping.period_degree, 2);
+        assert!(mapping.keys.is_empty());
+        let mapping = parse_mapping("2\n0\n127\n60\n69\n440.0\n1\n0\nx\n").unwrap();
+        assert_eq!(mapping.keys, vec![Some(0), None]);
+        assert!(parse_mapping("3\n0\n127\n60\n69\n440.0\n1\n0\n").is_err());
+        assert!(parse_mapping("0\n0\n127\n").is_err());
+    }
+
+    #[test]
+    fn keys_play_the_nearest_semitone() {
+        let fifths = tuning(FIFTHS, ON_MIDDLE_C);
+        let (pitch, cents) = fifths.key_pitch(Pitch::new(C, 4), 0.).unwrap();
+        assert_eq!(pitch, Pitch::new(C, 4));
+        assert!(cents.abs() < 0.01);
+        let (pitch, cents) = fifths.key_pitch(Pitch::new(D, 4), 0.).unwrap();
+        assert_eq!(pitch, Pitch::new(G, 4));
+        assert!((cents - 1.955).abs() < 0.01);
+        let (pitch, _) = fifths.key_pitch(Pitch::new(E, 4), 0.).unwrap();
+        assert_eq!(pitch, Pitch::new(C, 5));
+        assert_eq!(fifths.key_pitch(Pitch::new(Cs, 4), 0.), None);
+    }
+
+    #[test]
+    fn keys_rounding_to_the_same_semitone_are_found() {
+        assert_eq!(tuning(FIFTHS, ON_MIDDLE_C).same_pitch_keys(), None);
+        let edo_19 = (1..=19).map(|step| format!("{:.4}\n", 1200. * step as f64 / 19.)).collect::<String>();
+        let edo_19 = tuning(format!("19-EDO\n19\n{}", edo_19).as_str(), ON_MIDDLE_C);
+        assert_eq!(edo_19.same_pitch_keys(), Some(("W", "E")));
+    }
+
+    #[test]
+    fn silent_keys_play_nothing() {
+        let fifths = tuning(FIFTHS, "2\n0\n127\n60\n60\n261.6255653\n2\n0\nx\n");
+        assert!(fifths.key_pitch(Pitch::new(C, 4), 0.).is_some());
+        assert_eq!(fifths.key_pitch(Pitch::new(D, 4), 0.), None);
+    }
+}
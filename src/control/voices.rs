@@ -0,0 +1,216 @@
+use std::time::Instant;
+use rust_synth::core::{
+    control::{synth::{Command::*, Id}, tools::Command::{self, *}},
+    music_theory::pitch::Pitch,
+};
+
+pub const MAX_VOICES: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Allocation {
+    Poly,
+    /// One note at a time, restruck as keys are pressed and released
+    Mono,
+}
+
+/// Note let go of when a new one needs its voice
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stealing {
+    Oldest, Lowest
+}
+
+/// Held key that plays in mono
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Priority {
+    Last, Low, High
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Note {
+    id: Id,
+    pitch: Pitch,
+    velocity: f64,
+    since: Instant,
+}
+
+/// Decides which of the notes played from the keyboard sound, as the synth plays every note it's sent
+pub struct Voices {
+    pub allocation: Allocation,
+    /// Notes sounding at once in poly
+    pub limit: usize,
+    pub stealing: Stealing,
+    pub priority: Priority,
+    /// Keys held, in the order they were pressed
+    held: Vec<Note>,
+    sounding: Vec<Note>,
+}
+
+impl Default for Voices {
+    fn default() -> Self {
+        Voices {
+            allocation: Allocation::Poly,
+            limit: MAX_VOICES,
+            stealing: Stealing::Oldest,
+            priority: Priority::Last,
+            held: vec![],
+            sounding: vec![],
+        }
+    }
+}
+
+impl Voices {
+
+    pub fn note_on(&mut self, id: Id, pitch: Pitch, velocity: f64, now: Instant) -> Vec<Command> {
+        let note = Note { id, pitch, velocity, since: now };
+        self.held.retain(|held| held.id != id);
+        self.held.push(note);
+        match self.allocation {
+            Allocation::Poly => {
+                let mut commands = vec![];
+                while self.sounding.len() >= self.limit.max(1) {
+                    commands.extend(self.steal());
+                }
+                commands.extend(self.start(note));
+                commands
+            },
+            Allocation::Mono => self.play_priority(),
+        }
+    }
+
+    /// Stops a note, or in mono goes back to the key still held that has the priority
+    pub fn note_off(&mut self, id: Id) -> Vec<Command> {
+        self.held.retain(|held| held.id != id);
+        let mut commands = self.stop(id);
+        if self.allocation == Allocation::Mono && self.sounding.is_empty() {
+            commands.extend(self.play_priority());
+        }
+        commands
+    }
+
+    /// Notes sounding and their velocity, to be restruck when they're transposed
+    pub fn sounding(&self) -> Vec<(Id, Pitch, f64)> {
+        self.sounding.iter().map(|note| (note.id, note.pitch, note.velocity)).collect()
+    }
+
+    /// Starts the held key that has the priority, in place of the note sounding
+    fn play_priority(&mut self) -> Vec<Command> {
+        let chosen = match self.priority {
+            Priority::Last => self.held.last(),
+            Priority::Low => self.held.iter().min_by(|a, b| a.pitch.freq().total_cmp(&b.pitch.freq())),
+            Priority::High => self.held.iter().max_by(|a, b| a.pitch.freq().total_cmp(&b.pitch.freq())),
+        }.copied();
+        match chosen {
+            Some(note) if !self.sounding.iter().any(|sounding| sounding.id == note.id) => {
+                let mut commands = self.stop_all();
+                commands.extend(self.start(note));
+                commands
+            },
+            _ => vec![],
+        }
+    }
+
+    fn steal(&mut self) -> Vec<Command> {
+        let victim = match self.stealing {
+            Stealing::Oldest => self.sounding.iter().min_by_key(|note| note.since),
+            Stealing::Lowest => self.sounding.iter().min_by(|a, b| a.pitch.freq().total_cmp(&b.pitch.freq())),
+        }.map(|note| note.id);
+        victim.map_or(vec![], |id| self.stop(id))
+    }
+
+    fn start(&mut self, note: Note) -> Vec<Command> {
+        self.sounding.push(note);
+        vec![Instrument(NoteOn(note.pitch, note.velocity, note.id))]
+    }
+
+    fn stop(&mut self, id: Id) -> Vec<Command> {
+        let before = self.sounding.len();
+        self.sounding.retain(|note| note.id != id);
+        if self.sounding.len() < before { vec![Instrument(NoteOff(id))] } else { vec![] }
+    }
+
+    fn stop_all(&mut self) -> Vec<Command> {
+        self.sounding.drain(..).map(|note| Instrument(NoteOff(note.id))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use rust_synth::core::{control::synth::id_discr, music_theory::pitch_class::PitchClass::{self, *}};
+
+    fn pitch(class: PitchClass) -> Pitch {
+        Pitch::new(class, 4)
+    }
+
+    fn id(pitch: Pitch) -> Id {
+        id_discr(pitch, 1)
+    }
+
+    /// Ids of the notes started and stopped
+    fn ons_offs(commands: &[Command]) -> (Vec<Id>, Vec<Id>) {
+        let mut ons_offs = (vec![], vec![]);
+        for command in commands {
+            match command {
+                Instrument(NoteOn(_, _, id)) => ons_offs.0.push(*id),
+                Instrument(NoteOff(id)) => ons_offs.1.push(*id),
+                _ => (),
+            }
+        }
+        ons_offs
+    }
+
+    fn play(voices: &mut Voices, pitch: Pitch, velocity: f64, since: Instant) -> (Vec<Id>, Vec<Id>) {
+        ons_offs(&voices.note_on(id(pitch), pitch, velocity, since))
+    }
+
+    #[test]
+    fn poly_steals_when_over_the_limit() {
+        let now = Instant::now();
+        let later = |ms| now + Duration::from_millis(ms);
+        for (stealing, victim) in [(Stealing::Oldest, E), (Stealing::Lowest, C)].iter() {
+            let mut voices = Voices { limit: 2, stealing: *stealing, ..Default::default() };
+            play(&mut voices, pitch(E), 1., later(0));
+            play(&mut voices, pitch(C), 1., later(1));
+            let (ons, offs) = play(&mut voices, pitch(G), 1., later(2));
+            assert_eq!(ons, vec![id(pitch(G))]);
+            assert_eq!(offs, vec![id(pitch(*victim))], "{:?}", stealing);
+            assert_eq!(voices.sounding().len(), 2);
+        }
+    }
+
+    #[test]
+    fn mono_plays_one_note_and_goes_back_to_the_held_one() {
+        let now = Instant::now();
+        let mut voices = Voices { allocation: Allocation::Mono, ..Default::default() };
+        play(&mut voices, pitch(C), 1., now);
+        let (ons, offs) = play(&mut voices, pitch(E), 1., now);
+        assert_eq!((ons, offs), (vec![id(pitch(E))], vec![id(pitch(C))]));
+        let (ons, offs) = ons_offs(&voices.note_off(id(pitch(E))));
+        assert_eq!((ons, offs), (vec![id(pitch(C))], vec![id(pitch(E))]));
+        let (ons, offs) = ons_offs(&voices.note_off(id(pitch(C))));
+        assert_eq!((ons, offs), (vec![], vec![id(pitch(C))]));
+    }
+
+    #[test]
+    fn mono_priority() {
+        let now = Instant::now();
+        for (priority, playing) in [(Priority::Last, E), (Priority::Low, C), (Priority::High, G)].iter() {
+            let mut voices = Voices { allocation: Allocation::Mono, priority: *priority, ..Default::default() };
+            play(&mut voices, pitch(G), 1., now);
+            play(&mut voices, pitch(C), 1., now);
+            play(&mut voices, pitch(E), 1., now);
+            let sounding = voices.sounding().iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+            assert_eq!(sounding, vec![id(pitch(*playing))], "{:?}", priority);
+        }
+    }
+
+    #[test]
+    fn releasing_a_silent_key_stops_nothing() {
+        let now = Instant::now();
+        let mut voices = Voices { allocation: Allocation::Mono, priority: Priority::Low, ..Default::default() };
+        play(&mut voices, pitch(C), 1., now);
+        play(&mut voices, pitch(E), 1., now);
+        assert_eq!(ons_offs(&voices.note_off(id(pitch(E)))), (vec![], vec![]));
+    }
+}
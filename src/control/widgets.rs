@@ -0,0 +1,191 @@
+use rust_synth::core::synth::oscillator;
+use crate::control::{Control, Mode, EditTarget, params::Param, modulation::Axis,
+                     scales::{self, INTERVALS}, voices::Allocation};
+
+/// x, y, width, height
+pub type Rect = [f64; 4];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+    Knob, Slider, Toggle, Dropdown
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Widget {
+    pub param: Param,
+    pub kind: Kind,
+    pub label: &'static str,
+    pub rect: Rect,
+}
+
+/// Mouse gesture started by clicking on a knob or slider
+#[derive(Copy, Clone, Debug)]
+pub struct Drag {
+    pub widget: Widget,
+    pub last: [f64; 2],
+    /// Normalized value accumulated along the drag, before the param rounds it
+    pub value: f64,
+}
+
+const LEFT: f64 = 40.;
+const RIGHT: f64 = 760.;
+const TOP: f64 = 320.;
+/// Room for the label above a widget and its value below
+const ROW_MARGIN: f64 = 50.;
+const SPACING: f64 = 40.;
+
+type Row = Vec<(Param, Kind, &'static str)>;
+
+/// Widgets for the page being edited, positioned in window coordinates
+pub fn layout(control: &Control) -> Vec<Widget> {
+    let mut widgets = vec![];
+    let mut y = TOP;
+    for row in rows(control) {
+        let row_height = row.iter().map(|(_, kind, _)| size(*kind)[1]).fold(0., f64::max) + ROW_MARGIN;
+        let mut x = LEFT;
+        for (param, kind, label) in row {
+            let [width, height] = size(kind);
+            if x + width > RIGHT {
+                x = LEFT;
+                y += row_height;
+            }
+            widgets.push(Widget { param, kind, label, rect: [x, y, width, height] });
+            x += width + SPACING;
+        }
+        y += row_height;
+    }
+    widgets
+}
+
+fn rows(control: &Control) -> Vec<Row> {
+    match control.mode {
+        Mode::Editing(Some(EditTarget::Oscillator(_))) => oscillator_rows(control),
+        Mode::Editing(Some(EditTarget::Matrix)) => matrix_rows(control),
+        Mode::Editing(Some(EditTarget::MouseXY)) => vec![mouse_row(Axis::X), mouse_row(Axis::Y)],
+        Mode::Editing(Some(EditTarget::Scale)) => scale_rows(control),
+        _ => vec![params(control)],
+    }
+}
+
+fn params(control: &Control) -> Row {
+    use {EditTarget::*, Param::*, Kind::*};
+    match control.mode {
+        Mode::Editing(Some(Filter)) =>
+            vec![(FilterType, Dropdown, "type"), (FilterCutoff, Knob, "cutoff"), (FilterResonance, Knob, "resonance")],
+        Mode::Editing(Some(Arpeggiator)) => {
+            let mut params = vec![(ArpOn, Toggle, "on"), (ArpChord, Dropdown, "chord"),
+                                  (ArpDirection, Dropdown, "direction")];
+            if control.arpeggiator.is_some() {
+                params.push((ArpOctaveMin, Slider, "lowest octave"));
+                params.push((ArpOctaveMax, Slider, "highest octave"));
+            }
+            params
+        },
+        Mode::Editing(Some(LFO)) => {
+            let mut params = vec![(LfoSelect, Knob, "lfo"), (LfoOn, Toggle, "on"),
+                                  (LfoWaveform, Dropdown, "waveform"), (LfoSync, Toggle, "sync")];
+            if control.modulation.lfos[control.selected_lfo].sync.is_some() {
+                params.push((LfoDivision, Knob, "rate"));
+            } else {
+                params.push((LfoFreq, Knob, "frequency"));
+            }
+            if control.value(LfoOn) > 0.5 {
+                params.push((LfoTarget, Dropdown, "target"));
+                params.push((LfoAmount, Knob, "amount"));
+            }
+            params
+        },
+        Mode::Editing(Some(Envelope)) =>
+            vec![(EnvAttack, Knob, "attack"), (EnvDecay, Knob, "decay"), (EnvSustain, Knob, "sustain"),
+                 (EnvRelease, Knob, "release")],
+        Mode::Editing(Some(Tempo)) =>
+            vec![(Quantize, Dropdown, "quantize"), (Swing, Knob, "swing"), (Metronome, Toggle, "flash beats"),
+                 (CountIn, Toggle, "count-in"), (BeatsPerBar, Knob, "time")],
+        Mode::Editing(Some(Patches)) => vec![(PatchSelect, Dropdown, "patch"), (PatchLevel, Knob, "level")],
+        Mode::Editing(Some(Loops)) => vec![(LoopSelect, Knob, "loop"), (LoopBars, Knob, "length in bars")],
+        Mode::Editing(Some(Voices)) => match control.voices.allocation {
+            Allocation::Poly => vec![(VoiceAllocation, Dropdown, "voices"), (VoiceLimit, Knob, "limit"),
+                                     (VoiceStealing, Dropdown, "steal")],
+            _ => vec![(VoiceAllocation, Dropdown, "voices"), (VoicePriority, Dropdown, "priority")],
+        },
+        _ => vec![],
+    }
+}
+
+fn oscillator_rows(control: &Control) -> Vec<Row> {
+    use {Param::*, Kind::*};
+    let mut rows = vec![];
+    match control.instrument().oscillator {
+        oscillator::Specs::Pulse(_) =>
+            rows.push(vec![(Waveform, Dropdown, "waveform"), (DutyCycle, Slider, "duty cycle")]),
+        oscillator::Specs::Mix { .. } => {
+            rows.push(vec![(Waveform, Dropdown, "waveform"), (MixVoices, Knob, "voices"), (MixDetune, Knob, "detune")]);
+            rows.push(vec![(MixWaveform, Dropdown, "mix of"), (MixSeed, Knob, "seed"),
+                           (MixSeedLock, Toggle, "lock seed")]);
+        },
+        _ => rows.push(vec![(Waveform, Dropdown, "waveform")]),
+    }
+    rows
+}
+
+fn matrix_rows(control: &Control) -> Vec<Row> {
+    use {Param::*, Kind::*};
+    (0..control.modulation.routes.len()).map(|i|
+        vec![(RouteSource(i), Dropdown, "source"), (RouteDestination(i), Dropdown, "destination"),
+             (RouteAmount(i), Slider, "amount")]
+    ).collect()
+}
+
+/// The scale and reference pitch, with the intervals of the custom scale when it's the one selected
+fn scale_rows(control: &Control) -> Vec<Row> {
+    use {Param::*, Kind::*};
+    let mut rows = vec![vec![(ScaleSelect, Dropdown, "scale"), (Reference, Knob, "A4")]];
+    if control.scale.selected == scales::CUSTOM {
+        rows.push(INTERVALS.iter().enumerate().map(|(i, label)| (ScaleNote(i + 1), Toggle, *label)).collect());
+    }
+    rows
+}
+
+fn mouse_row(axis: Axis) -> Row {
+    use {Param::*, Kind::*};
+    let target = match axis {
+        Axis::X => "x target",
+        Axis::Y => "y target",
+    };
+    vec![(MouseDestination(axis), Dropdown, target), (MouseCurve(axis), Dropdown, "curve"),
+         (MouseMin(axis), Knob, "from"), (MouseMax(axis), Knob, "to")]
+}
+
+fn size(kind: Kind) -> [f64; 2] {
+    match kind {
+        Kind::Knob => [60., 60.],
+        Kind::Slider => [200., 16.],
+        Kind::Toggle => [50., 24.],
+        Kind::Dropdown => [120., 24.],
+    }
+}
+
+pub fn hit(widgets: &[Widget], x: f64, y: f64) -> Option<Widget> {
+    widgets.iter().find(|w| contains(w.rect, x, y)).copied()
+}
+
+/// Index of the option clicked in an open dropdown
+pub fn hit_option(widget: &Widget, x: f64, y: f64) -> Option<usize> {
+    (0..widget.param.options().len()).find(|i| contains(option_rect(widget, *i), x, y))
+}
+
+pub fn option_rect(widget: &Widget, index: usize) -> Rect {
+    let [x, y, width, height] = widget.rect;
+    [x, y + height * (index + 1) as f64, width, height]
+}
+
+/// Normalized value under the mouse along a slider
+pub fn slider_value(widget: &Widget, x: f64) -> f64 {
+    let [left, _, width, _] = widget.rect;
+    ((x - left) / width).clamp(0., 1.)
+}
+
+fn contains(rect: Rect, x: f64, y: f64) -> bool {
+    let [left, top, width, height] = rect;
+    x >= left && x <= left + width && y >= top && y <= top + height
+}
@@ -1,7 +1,8 @@
 use std::path::Path;
+use std::time::Instant;
 use std::sync::mpsc::{Receiver, Sender};
 
-use piston_window::{Event::*, Glyphs, Loop::*, OpenGL, PistonWindow, TextureSettings, WindowSettings};
+use piston_window::{Event::*, EventLoop, Glyphs, Loop::*, OpenGL, PistonWindow, TextureSettings, WindowSettings};
 use rust_synth::core::control::tools::{Command, View};
 
 use crate::control::Control;
@@ -9,6 +10,8 @@ use crate::rendering;
 
 const TITLE: &str = "Sintetizador Maravilhoso";
 const WINDOW_SIZE: [f64;2] = [800., 800.];
+/// Updates per second, timing the notes the GUI holds back or plays by itself to within 5 ms
+const UPS: u64 = 200;
 
 pub fn start(channels: Option<(Sender<Command>, Receiver<View>)>) {
     let opengl = OpenGL::V3_2;
@@ -28,23 +31,37 @@ pub fn start(channels: Option<(Sender<Command>, Receiver<View>)>) {
     }
 }
 
+/// Views are followed and the timed commands sent on updates, which keep coming when the window isn't drawn,
+/// while renders draw the last view received
 fn manual_loop(window: &mut PistonWindow, glyphs: &mut Glyphs, commands_out: Sender<Command>, view_in: Receiver<View>) {
     let mut control = Control::new();
+    let mut last_view = None;
+    window.set_ups(UPS);
     while let Some(e) = window.next() {
-        match &e {
-            Input(input) => {
-                for command in control.handle_input(input, WINDOW_SIZE) {
-                    commands_out.send(command).expect("Failed to send synth command")
+        let commands = match &e {
+            Input(input) => control.handle_input(input, WINDOW_SIZE),
+            Loop(Update(_)) => {
+                let mut commands = vec![];
+                for view in view_in.try_iter() {
+                    commands.extend(control.handle_view(&view));
+                    last_view = Some(view);
                 }
+                commands.extend(control.tick(Instant::now()));
+                commands
             },
             Loop(Render(_)) => {
-                if let Ok(view) = view_in.try_recv() {
-                    rendering::draw(view, control.mode, window, glyphs, &e)
+                if let Some(view) = last_view.take() {
+                    rendering::draw(view, &control, window, glyphs, &e)
                 }
-            }
-            _ => (),
+                vec![]
+            },
+            _ => vec![],
+        };
+        for command in commands {
+            commands_out.send(command).expect("Failed to send synth command")
         }
     }
+    control.save_config();
 }
 
 fn midi_loop(window: &mut PistonWindow) {
@@ -1,39 +1,59 @@
 use std::collections::HashMap;
-use piston_window::{PistonWindow, Event, Context, G2d, clear, text, rectangle, Glyphs, Transformed};
+use std::time::Instant;
+use piston_window::{PistonWindow, Event, Context, G2d, clear, text, rectangle, line, Rectangle, Ellipse, Glyphs,
+                    Transformed};
 use piston_window::math::Scalar;
 use rust_synth::core::control::tools;
 use rust_synth::core::control::synth::Id;
-use rust_synth::core::synth::{filter, oscillator, lfo};
+use rust_synth::core::synth::{filter, oscillator, lfo, adsr};
 use rust_synth::core::tools::{arpeggiator, transposer, loops, pulse};
 use rust_synth::core::music_theory::{Hz, pitch::Pitch, diatonic_scale, rhythm::Note};
-use crate::control::{Mode, EditTarget, OscillatorTarget};
+use crate::control::{Control, Mode, EditTarget, OscillatorTarget, keymap, widgets::{self, Widget, Kind},
+                     tempo::{self, Division}, params::Param, modulation::{Source, Axis},
+                     sequencer::Step, rhythm_grid::{Row, Hit}, song::Field};
 use rust_synth::core::synth::instrument::ModTarget;
 
 pub type Color = [f32; 4];
 const BLACK: Color = [0.0, 0.0, 0.0, 1.0];
 const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
 
-pub fn draw(view: tools::View, mode: Mode, window: &mut PistonWindow, glyphs: &mut Glyphs, e: &Event) {
+pub fn draw(view: tools::View, control: &Control, window: &mut PistonWindow, glyphs: &mut Glyphs, e: &Event) {
     window.draw_2d(e, |c: Context, g: &mut G2d| {
         clear(BLACK, g);
         draw_text("~ Sintetizador Maravilhoso ~", 250., 40., glyphs, c, g);
-        draw_mode(mode, 10., 80., glyphs, c, g);
+        draw_mode(control.mode, 10., 80., glyphs, c, g);
 
         draw_volume(view.synth.instrument.volume, 670., 120., glyphs, c, g);
         draw_oscillator(view.synth.instrument.oscillator, 10., 120., glyphs, c, g);
         draw_filter(view.synth.instrument.filter, 10., 145., glyphs, c, g);
 
         if let Some(lfo) = view.synth.instrument.lfo {
-            draw_lfo(lfo, 10., 170., glyphs, c, g);
+            let sync = control.modulation.played_lfo().and_then(|lfo| lfo.sync);
+            draw_lfo(lfo, sync, 10., 170., glyphs, c, g);
         }
 
         if let Some(arp) = view.arpeggiator {
             draw_arpeggiator(arp, view.arp_index, 10., 200., glyphs, c, g);
         }
 
-        draw_pulse(view.pulse, 680., 700., glyphs, c, g);
-        draw_loops(view.loops, 10., 700., glyphs, c, g);
-        draw_transposer(view.transposer, 10., 740., glyphs, c, g);
+        match control.mode {
+            Mode::Playing => {
+                draw_keyboard(view.transposer, control, 100., 480., glyphs, c, g);
+                if !control.mouse_transposes {
+                    draw_mouse_xy(control, 640., 250., glyphs, c, g);
+                }
+            },
+            Mode::Editing(_) => draw_widgets(control, glyphs, c, g),
+            Mode::Sequencing => draw_sequencer(control, 40., 320., glyphs, c, g),
+            Mode::Rhythm => draw_rhythm_grid(control, 40., 320., glyphs, c, g),
+            Mode::Arranging => draw_song(control, 40., 320., glyphs, c, g),
+        }
+
+        draw_pulse(view.pulse, control, 620., 700., glyphs, c, g);
+        draw_loops(view.loops, control, 10., 700., glyphs, c, g);
+        draw_transposer(view.transposer, control, 10., 740., glyphs, c, g);
+        draw_step_transpose(control, 400., 740., glyphs, c, g);
+        draw_patches(control, 400., 720., glyphs, c, g);
         draw_notes(view.synth.holding_notes, 10., 760., glyphs, c, g);
     });
 }
@@ -52,9 +72,20 @@ pub fn draw_mode(mode: Mode, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Conte
                 Some(EditTarget::Filter) => "editing > filter",
                 Some(EditTarget::Arpeggiator) => "editing > arpeggiator",
                 Some(EditTarget::LFO) => "editing > lfo",
+                Some(EditTarget::Matrix) => "editing > modulation matrix",
+                Some(EditTarget::Envelope) => "editing > envelope",
+                Some(EditTarget::MouseXY) => "editing > mouse xy",
+                Some(EditTarget::Patches) => "editing > patches",
+                Some(EditTarget::Loops) => "editing > loops",
+                Some(EditTarget::Tempo) => "editing > tempo",
+                Some(EditTarget::Scale) => "editing > scale",
+                Some(EditTarget::Voices) => "editing > voices",
             }
         },
         Mode::Playing => "playing",
+        Mode::Sequencing => "step sequencer",
+        Mode::Rhythm => "rhythm grid",
+        Mode::Arranging => "arrangement",
     };
     draw_text(text, x, y, glyphs, c, g);
 }
@@ -92,7 +123,7 @@ pub fn draw_filter(view: filter::View, x: Scalar, y: Scalar, glyphs: &mut Glyphs
     draw_meter_vertical(view.resonance, x + 300., y, c, g);
 }
 
-pub fn draw_lfo(view: lfo::View, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+pub fn draw_lfo(view: lfo::View, sync: Option<Division>, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
     use ModTarget::*;
     use filter::ModTarget::*;
     use oscillator::ModTarget::*;
@@ -106,8 +137,13 @@ pub fn draw_lfo(view: lfo::View, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: C
     draw_text(format!("LFO: {}", target).as_str(), x, y, glyphs, c, g);
     draw_text("amount: ", x + 300., y, glyphs, c, g);
     draw_meter_vertical(view.amount, x + 380., y, c, g);
-    draw_text("frequency: ", x + 440., y, glyphs, c, g);
-    draw_meter_vertical(view.freq, x + 560., y, c, g);
+    match sync {
+        Some(division) => draw_text(format!("rate: {}", division).as_str(), x + 440., y, glyphs, c, g),
+        None => {
+            draw_text("frequency: ", x + 440., y, glyphs, c, g);
+            draw_meter_vertical(view.freq, x + 560., y, c, g);
+        },
+    }
     draw_oscillator(view.oscillator, x + 600., y, glyphs, c, g);
 }
 
@@ -135,46 +171,363 @@ fn draw_phrase(phrase: Vec<Note>, x: Scalar, y: Scalar, c: Context, g: &mut G2d)
     }
 }
 
-fn draw_pulse(view: pulse::View, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
-    let pulse = format!("tempo: {}", view.period.as_millis().to_string());
+fn draw_pulse(view: pulse::View, control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let pulse = format!("{:.1} bpm {}/4", tempo::bpm(view.period), control.metronome.beats_per_bar);
     draw_text(pulse.as_str(), x, y, glyphs, c, g);
+    if let Some(downbeat) = control.metronome.flash(&control.clock) {
+        let size = if downbeat { 12. } else { 6. };
+        draw_rectangle(size, size, x + 130., y - 12., c, g);
+    }
+    if let Some(bpm) = control.tapper.target() {
+        draw_text(format!("tapping {:.0} bpm", bpm).as_str(), x, y - 60., glyphs, c, g);
+    }
+    if control.metronome.is_counting_in() {
+        draw_text("count-in", x, y - 40., glyphs, c, g);
+    }
+    let grid = Param::Quantize.options()[control.selected(Param::Quantize)];
+    let quantize = match control.quantizer.grid {
+        Some(_) => format!("quantize: {} {}", grid, control.display(Param::Swing)),
+        None => "quantize: off".to_string(),
+    };
+    draw_text(quantize.as_str(), x, y - 20., glyphs, c, g);
 }
 
-fn draw_loops(view: loops::View, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+/// Loops playing (.) and recording (*), with their length in bars and playhead
+fn draw_loops(view: loops::View, control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
     draw_text("loops:", x, y, glyphs, c, g);
-    let loops: Vec<usize> = vec![0, 1, 2, 3, 4];
-    let mut offset = 70.;
-    for index in loops {
-        draw_text(format!("{}", index + 1).as_str(), x + offset, y, glyphs, c, g);
-        if let Some(true) = view.playing_loops.get(&index) {
-            draw_text(".", x + offset + 8., y, glyphs, c, g);
-        }
-        if let Some(recording) = view.recording_loop {
-            if recording == index {
-                draw_text("*", x + offset + 8., y, glyphs, c, g);
-            }
+    let now = Instant::now();
+    for (index, slot) in control.loops.iter().enumerate() {
+        let slot_x = x + 70. + 80. * index as f64;
+        let state = if view.recording_loop == Some(index) {
+            "*"
+        } else if let Some(true) = view.playing_loops.get(&index) {
+            "."
+        } else {
+            ""
+        };
+        let mut label = format!("{}{}", index + 1, state);
+        if let Some(bars) = control.bar().and_then(|bar| slot.length_in_bars(bar)) {
+            label.push_str(format!(" {:.1}b", bars).as_str());
+        }
+        draw_text(label.as_str(), slot_x, y, glyphs, c, g);
+        if let Some(playhead) = slot.playhead(now) {
+            draw_box([slot_x, y + 8., 60., 3.], c, g);
+            draw_rectangle(2., 3., slot_x + 60. * playhead, y + 8., c, g);
         }
-        offset += 20.;
     }
 }
 
-fn draw_transposer(view: transposer::State, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
-    let transposer = format!("key: {}, pitch shift: {}", view.transposed_key,
-                             view.pitch_shift);
+/// Key and scale or tuning, with the reference pitch when it's not the synth's
+fn draw_transposer(view: transposer::State, control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let scale = control.tuning.as_ref().map_or(control.scale.name(), |tuning| tuning.description.as_str());
+    let mut transposer = format!("key: {} {}, pitch shift: {}", view.transposed_key, scale,
+                                 view.pitch_shift);
+    let config = &control.config;
+    if config.semitones() != 0 {
+        transposer.push_str(format!(", A4 {:.0} Hz", config.reference).as_str());
+    }
     draw_text(transposer.as_str(), x, y, glyphs, c, g);
 }
 
+fn draw_keyboard(view: transposer::State, control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let pressed = &control.pressed_keys;
+    let row_offsets = [0., 15., 45.];
+    for (r, row) in keymap::ROWS.iter().enumerate() {
+        let row_y = y + 50. * r as f64;
+        for (i, (key, label)) in row.iter().enumerate() {
+            let key_x = x + row_offsets[r] + 60. * i as f64;
+            let color = if pressed.contains(key) {
+                draw_rectangle(54., 44., key_x, row_y, c, g);
+                BLACK
+            } else {
+                draw_box([key_x, row_y, 54., 44.], c, g);
+                WHITE
+            };
+            draw_text_color(label, color, key_x + 5., row_y + 18., glyphs, c, g);
+            if let Some((pitch, _)) = control.pitches(*key) {
+                let note = match control.cents_off(*key) {
+                    Some(cents) => format!("{} {:+.0}", view.transpose(pitch), cents),
+                    None => format!("{}", view.transpose(pitch)),
+                };
+                draw_text_color(note.as_str(), color, key_x + 5., row_y + 38., glyphs, c, g);
+            }
+        }
+    }
+}
+
+fn draw_widgets(control: &Control, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let widgets = widgets::layout(control);
+    if !widgets.is_empty() {
+        let mouse = if control.relative_mouse { "relative" } else { "absolute" };
+        draw_text(format!("mouse: {} (R), shift: fine adjust, up/down: nudge", mouse).as_str(), 40., 270., glyphs, c, g);
+    }
+    for widget in widgets.iter() {
+        let [x, y, _, _] = widget.rect;
+        let label = if control.focus == Some(widget.param) { format!("> {}", widget.label) } else { widget.label.to_string() };
+        draw_text(label.as_str(), x, y - 8., glyphs, c, g);
+        match widget.kind {
+            Kind::Knob => {
+                draw_knob(control.value(widget.param), widget.rect, c, g);
+                draw_text(control.display(widget.param).as_str(), x, y + widget.rect[3] + 20., glyphs, c, g);
+            },
+            Kind::Slider => {
+                draw_slider(control.value(widget.param), widget.rect, c, g);
+                draw_text(control.display(widget.param).as_str(), x, y + widget.rect[3] + 20., glyphs, c, g);
+            },
+            Kind::Toggle => draw_toggle(control.value(widget.param) > 0.5, widget.rect, glyphs, c, g),
+            Kind::Dropdown => {
+                let options = widget.param.options();
+                let selected = options.get(control.selected(widget.param)).unwrap_or(&"");
+                draw_box(widget.rect, c, g);
+                draw_text(format!("{} v", selected).as_str(), x + 5., y + 18., glyphs, c, g);
+            },
+        }
+    }
+    draw_unplayed_routes(control, &widgets, glyphs, c, g);
+    if let Mode::Editing(Some(EditTarget::Envelope)) = control.mode {
+        draw_envelope(control.envelope(), 40., 560., c, g);
+    }
+    if let Mode::Editing(Some(EditTarget::Tempo)) = control.mode {
+        let typed = format!("bpm: {}_ (type and enter, -/= nudge, [/] halve/double)", control.typed_bpm);
+        draw_text(typed.as_str(), 40., 480., glyphs, c, g);
+    }
+    if let Mode::Editing(Some(EditTarget::Scale)) = control.mode {
+        let tuning = "tuning: enter loads tuning.scl and tuning.kbm, rounded to semitones, backspace goes back to the scale";
+        draw_text(tuning, 40., 480., glyphs, c, g);
+        if let Some(status) = &control.tuning_status {
+            draw_text(status.as_str(), 40., 500., glyphs, c, g);
+        }
+    }
+    let open = control.open_dropdown.and_then(|param| widgets.iter().find(|w| w.param == param));
+    if let Some(widget) = open {
+        draw_options(widget, control.selected(widget.param), glyphs, c, g);
+    }
+}
+
+/// Points out the routes the synth leaves inactive, with the reason why
+fn draw_unplayed_routes(control: &Control, widgets: &[Widget], glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let modulation = &control.modulation;
+    for widget in widgets.iter() {
+        let note = match widget.param {
+            Param::RouteAmount(i) => modulation.inactive_reason(i),
+            Param::LfoAmount => modulation.route_from(Source::Lfo(control.selected_lfo))
+                .and_then(|i| modulation.inactive_reason(i)),
+            _ => None,
+        };
+        if let Some(note) = note {
+            let [x, y, width, _] = widget.rect;
+            draw_text(note, x + width + 20., y + 16., glyphs, c, g);
+        }
+    }
+}
+
+/// Shape of an envelope over time, with the sustain held for a fixed width
+fn draw_envelope(specs: &adsr::Specs, x: Scalar, y: Scalar, c: Context, g: &mut G2d) {
+    let (width, height, sustain_width) = (480., 100., 120.);
+    let total = (specs.attack + specs.decay + specs.release).max(0.001);
+    let scale = (width - sustain_width) / total;
+    let sustain_level = y - specs.sustain * height;
+    let attack_x = x + specs.attack * scale;
+    let decay_x = attack_x + specs.decay * scale;
+    let release_x = decay_x + sustain_width;
+    let end_x = release_x + specs.release * scale;
+    let points = [[x, y], [attack_x, y - height], [decay_x, sustain_level], [release_x, sustain_level], [end_x, y]];
+    for segment in points.windows(2) {
+        let [x1, y1] = segment[0];
+        let [x2, y2] = segment[1];
+        line(WHITE, 1., [x1, y1, x2, y2], c.transform, g);
+    }
+}
+
+fn draw_knob(value: f64, rect: widgets::Rect, c: Context, g: &mut G2d) {
+    let [x, y, width, height] = rect;
+    let radius = width.min(height) / 2.;
+    let (center_x, center_y) = (x + width / 2., y + height / 2.);
+    let angle = (value * 1.5 - 0.75) * std::f64::consts::PI;
+    Ellipse::new_border(WHITE, 1.).draw(rect, &c.draw_state, c.transform, g);
+    let pointer = [center_x, center_y, center_x + radius * angle.sin(), center_y - radius * angle.cos()];
+    line(WHITE, 1., pointer, c.transform, g);
+}
+
+fn draw_slider(value: f64, rect: widgets::Rect, c: Context, g: &mut G2d) {
+    let [x, y, width, height] = rect;
+    draw_box(rect, c, g);
+    draw_rectangle(width * value, height, x, y, c, g);
+}
+
+fn draw_toggle(on: bool, rect: widgets::Rect, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let [x, y, width, height] = rect;
+    if on {
+        draw_rectangle(width, height, x, y, c, g);
+        draw_text_color("on", BLACK, x + 5., y + 18., glyphs, c, g);
+    } else {
+        draw_box(rect, c, g);
+        draw_text("off", x + 5., y + 18., glyphs, c, g);
+    }
+}
+
+fn draw_options(widget: &Widget, selected: usize, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    for (i, option) in widget.param.options().iter().enumerate() {
+        let rect = widgets::option_rect(widget, i);
+        let [x, y, width, height] = rect;
+        rectangle(BLACK, rect, c.transform, g);
+        let color = if i == selected {
+            draw_rectangle(width, height, x, y, c, g);
+            BLACK
+        } else {
+            draw_box(rect, c, g);
+            WHITE
+        };
+        draw_text_color(option, color, x + 5., y + 18., glyphs, c, g);
+    }
+}
+
+/// Steps of the pattern, 16 to a row, with their note, velocity as a bar and gate as its width.
+/// Ties are drawn as a line, the cursor as a thicker box and the step playing is filled.
+fn draw_sequencer(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let sequencer = &control.sequencer;
+    let playback = if sequencer.is_playing() { "playing" } else { "stopped" };
+    let header = format!("pattern {}, {} steps, {}", sequencer.pattern + 1, sequencer.steps().len(), playback);
+    draw_text(header.as_str(), x, y, glyphs, c, g);
+    let (width, height) = (42., 60.);
+    for (i, step) in sequencer.steps().iter().enumerate() {
+        let step_x = x + width * (i % 16) as f64;
+        let step_y = y + 20. + (height + 20.) * (i / 16) as f64;
+        if sequencer.playhead() == Some(i) {
+            draw_rectangle(width - 4., 6., step_x, step_y + height - 6., c, g);
+        }
+        let border = if sequencer.cursor == i { 3. } else { 1. };
+        Rectangle::new_border(WHITE, border).draw([step_x, step_y, width - 4., height], &c.draw_state, c.transform, g);
+        match step {
+            Step::Rest => (),
+            Step::Note { pitch, velocity, gate } => {
+                draw_text(format!("{}", pitch).as_str(), step_x + 3., step_y + 18., glyphs, c, g);
+                draw_rectangle((width - 10.) * gate, 30. * velocity, step_x + 3., step_y + 50. - 30. * velocity, c, g);
+            },
+            Step::Tie => line(WHITE, 2., [step_x, step_y + 30., step_x + width - 4., step_y + 30.], c.transform, g),
+        }
+    }
+    draw_text("notes enter steps, enter ties, backspace rests, arrows move", x, y + 200., glyphs, c, g);
+    draw_text("space plays, -/= velocity, [/] gate, ' length, 1-4 pattern", x, y + 220., glyphs, c, g);
+}
+
+/// Hits of each row over the steps, accents filled and plain hits half filled, with the pitch each row plays
+fn draw_rhythm_grid(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let grid = &control.rhythm_grid;
+    draw_text(if grid.is_playing() { "playing" } else { "stopped" }, x, y, glyphs, c, g);
+    let (left, size) = (x + 60., 36.);
+    for (v, (row, hits)) in Row::ALL.iter().zip(grid.hits.iter()).enumerate() {
+        let row_y = y + 20. + (size + 4.) * v as f64;
+        draw_text(format!("{}", row.pitch()).as_str(), x, row_y + 24., glyphs, c, g);
+        for (s, hit) in hits.iter().enumerate() {
+            let step_x = left + (size + 4.) * s as f64;
+            let border = if grid.cursor == (v, s) { 3. } else { 1. };
+            Rectangle::new_border(WHITE, border).draw([step_x, row_y, size, size], &c.draw_state, c.transform, g);
+            match hit {
+                Hit::Off => (),
+                Hit::On => draw_rectangle(size, size / 2., step_x, row_y + size / 2., c, g),
+                Hit::Accent => draw_rectangle(size, size, step_x, row_y, c, g),
+            }
+            if grid.playhead() == Some(s) && v == 0 {
+                draw_rectangle(size, 4., step_x, y + 12., c, g);
+            }
+        }
+    }
+    draw_text("rows play through the patch at their pitch", x, y + 200., glyphs, c, g);
+    draw_text("arrows move, enter cycles off/on/accent, space plays", x, y + 220., glyphs, c, g);
+}
+
+/// Sections along a timeline, as wide as their bars, with the song position and the selected section's settings
+fn draw_song(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let song = &control.song;
+    let position = song.position();
+    let header = match position {
+        Some((section, bars)) => format!("playing section {}, bar {}", section + 1, bars as u32 + 1),
+        None => "stopped".to_string(),
+    };
+    draw_text(header.as_str(), x, y, glyphs, c, g);
+    let (bar_width, height) = (10., 40.);
+    let mut section_x = x;
+    for (i, section) in song.sections.iter().enumerate() {
+        let width = bar_width * section.bars as f64;
+        let border = if song.selected == i { 3. } else { 1. };
+        Rectangle::new_border(WHITE, border).draw([section_x, y + 20., width - 4., height], &c.draw_state, c.transform, g);
+        draw_text(format!("{}", i + 1).as_str(), section_x + 3., y + 40., glyphs, c, g);
+        if let Some((_, bars)) = position.filter(|(playing, _)| *playing == i) {
+            let playhead_x = section_x + bar_width * bars;
+            line(WHITE, 2., [playhead_x, y + 16., playhead_x, y + 24. + height], c.transform, g);
+        }
+        section_x += width;
+    }
+    if let Some(section) = song.sections.get(song.selected) {
+        let loops = section.loops.iter().enumerate()
+            .map(|(n, on)| if *on { format!("{}", n + 1) } else { "-".to_string() })
+            .collect::<Vec<_>>();
+        draw_text(format!("section {}, loops {}", song.selected + 1, loops.join(" ")).as_str(), x, y + 90., glyphs, c, g);
+        for (f, field) in Field::ALL.iter().enumerate() {
+            let text = format!("{}: {}", field.name(), section.display(*field));
+            let field_x = x + 140. * f as f64;
+            draw_text(text.as_str(), field_x, y + 110., glyphs, c, g);
+            if song.field == *field {
+                line(WHITE, 1., [field_x, y + 114., field_x + 120., y + 114.], c.transform, g);
+            }
+        }
+    }
+    if let Some(status) = &song.status {
+        draw_text(status.as_str(), x, y + 140., glyphs, c, g);
+    }
+    draw_text("enter adds a section, backspace removes it, up/down select, space plays", x, y + 200., glyphs, c, g);
+    draw_text("left/right pick a setting, -/= change it, delete unsets it, 1-5 toggle loops", x, y + 220., glyphs, c, g);
+    draw_text("ctrl+s saves and ctrl+o loads song.txt", x, y + 240., glyphs, c, g);
+}
+
+/// Crosshair at the mouse position last sent to the XY targets, and what they are
+fn draw_mouse_xy(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let size = 120.;
+    let [mouse_x, mouse_y] = control.mouse_xy;
+    let (cross_x, cross_y) = (x + mouse_x * size, y + mouse_y * size);
+    draw_box([x, y, size, size], c, g);
+    line(WHITE, 0.5, [cross_x, y, cross_x, y + size], c.transform, g);
+    line(WHITE, 0.5, [x, cross_y, x + size, cross_y], c.transform, g);
+    for (i, axis) in [Axis::X, Axis::Y].iter().enumerate() {
+        let route = control.modulation.mouse_route(*axis);
+        let value = route.curve.to_value(control.mouse_xy[i]);
+        let name = Param::MouseDestination(*axis).options()[route.destination.index()];
+        let text = format!("{:?}: {} {:.2}", axis, name, value).to_lowercase();
+        draw_text(text.as_str(), x, y + size + 20. * (i + 1) as f64, glyphs, c, g);
+    }
+    if control.xy_frozen {
+        draw_text("frozen", x, y - 8., glyphs, c, g);
+    }
+}
+
+fn draw_patches(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let levels = control.patches.iter().map(|patch| format!("{:.2}", patch.level)).collect::<Vec<_>>();
+    let text = format!("patch: {}, levels: {}", control.selected_patch + 1, levels.join(" "));
+    draw_text(text.as_str(), x, y, glyphs, c, g);
+}
+
+fn draw_step_transpose(control: &Control, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    let mouse = if control.mouse_transposes { "step transpose" } else { "modulation" };
+    draw_text(format!("mouse: {}, step: {:+}", mouse, control.step_transpose).as_str(), x, y, glyphs, c, g);
+}
+
+/// Held notes with the frequency of their pitch, which leaves out the detune of a mix and the LFOs
 fn draw_notes(view: HashMap<Id, Pitch>, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
     let notes_vec = view.values()
-        .map(|pitch| format!("{}", pitch))
+        .map(|pitch| format!("{} {:.1} Hz", pitch, pitch.freq()))
         .collect::<Vec<_>>();
     let notes_str = format!("notes: {}", notes_vec.join(", "));
     draw_text(notes_str.as_str(), x, y, glyphs, c, g);
 }
 
 pub fn draw_text(text: &str, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
+    draw_text_color(text, WHITE, x, y, glyphs, c, g);
+}
+
+pub fn draw_text_color(text: &str, color: Color, x: Scalar, y: Scalar, glyphs: &mut Glyphs, c: Context, g: &mut G2d) {
     let c2 = c.trans(x, y).zoom(0.5);
-    text::Text::new_color(WHITE, 40)
+    text::Text::new_color(color, 40)
         .draw(text, glyphs, &c2.draw_state, c2.transform, g).unwrap();
 }
 
@@ -194,4 +547,8 @@ pub fn draw_rectangle(width: Scalar, height: Scalar, x: Scalar, y: Scalar, c: Co
     let c2 = c.trans(x, y);
     let rect = [0., 0., width, height];
     rectangle(WHITE, rect, c2.transform, g);
-}
\ No newline at end of file
+}
+
+pub fn draw_box(rect: [Scalar; 4], c: Context, g: &mut G2d) {
+    Rectangle::new_border(WHITE, 1.).draw(rect, &c.draw_state, c.transform, g);
+}